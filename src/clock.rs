@@ -0,0 +1,46 @@
+use std::cell::Cell;
+
+use chrono::{Duration, Utc};
+
+use crate::DateTime;
+
+/// Source of the current time
+pub trait Clock {
+    fn now(&self) -> DateTime;
+}
+
+/// The time of the system
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime {
+        Utc::now()
+    }
+}
+
+/// A time that only changes when it is set, for deterministic tests
+pub struct FixedClock {
+    now: Cell<DateTime>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime) -> Self {
+        Self {
+            now: Cell::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime {
+        self.now.get()
+    }
+}
@@ -0,0 +1,2843 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashSet},
+    fmt, fs,
+    io::{self, BufRead, Write},
+    mem,
+    path::PathBuf,
+    thread,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Duration, Local, NaiveTime, SubsecRound, Weekday};
+use clap_complete::env::Shells;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use regex::RegexBuilder;
+use serde_json::{json, Value};
+
+mod tui;
+
+use crate::{
+    clock::Clock,
+    data::{read_failed, Activity, ActivityInfo, Config, Data, Idle, IdleLog, Session},
+    hooks, ical,
+    idle::{IdleCommand, IdleFile, IdleSource},
+    parse_dt, parse_duration, parse_end, parse_start,
+    serve::Server,
+    stats::{convert_bounds, convert_bounds_all, read_in_range, recorded_range, summary_in_range},
+    storage::{self, Lock, Storage},
+    sync, Absolute, Bound, DateTime, Filter, Position,
+};
+
+/// Settings that can be changed with `track config`, as (name, default, description)
+const SETTINGS: [(&str, &str, &str); 16] = [
+    (
+        "work-start",
+        "09:00",
+        "start of working hours, used by track gaps",
+    ),
+    (
+        "work-end",
+        "17:00",
+        "end of working hours, used by track gaps",
+    ),
+    (
+        "work-days",
+        "mon,tue,wed,thu,fri",
+        "working days, used by track gaps",
+    ),
+    (
+        "no-overlap",
+        "false",
+        "whether sessions of different activities may not overlap",
+    ),
+    (
+        "max-session",
+        "off",
+        "length after which track end and track ongoing warn about an ongoing session",
+    ),
+    (
+        "daily-goal",
+        "off",
+        "time to track in an activity each day, after which ending a session runs the \"goal\" \
+         hook event",
+    ),
+    (
+        "idle-command",
+        "",
+        "shell command printing the number of seconds the user has been idle, used by track daemon",
+    ),
+    (
+        "idle-file",
+        "",
+        "file touched whenever the user is active, used by track daemon",
+    ),
+    (
+        "idle-threshold",
+        "10m",
+        "idle time after which track daemon ends the ongoing session",
+    ),
+    (
+        "idle-action",
+        "trim",
+        "whether track daemon should [trim] or [pause] the ongoing session when idle",
+    ),
+    (
+        "pomodoro-work",
+        "25m",
+        "length of each session of track pomodoro",
+    ),
+    (
+        "pomodoro-break",
+        "5m",
+        "length of the breaks between sessions of track pomodoro",
+    ),
+    (
+        "pomodoro-long-break",
+        "15m",
+        "length of the break after the last session of track pomodoro",
+    ),
+    (
+        "pomodoro-rounds",
+        "4",
+        "number of sessions of track pomodoro",
+    ),
+    (
+        "hook-command",
+        "",
+        "shell command run on each of \"hook-events\", which reads the event as JSON",
+    ),
+    (
+        "hook-events",
+        "start,end,cancel,switch,create,rename,delete,goal",
+        "events that run \"hook-command\"",
+    ),
+];
+
+/// Error for a command that asked the user something, during which another track command changed
+/// the data it read
+const CHANGED: &str =
+    "error: The data was changed by another track command while waiting for an answer, run the \
+     command again";
+
+/// Longest gap between two sessions that join includes in the joined session without asking
+const JOIN_GAP: Duration = Duration::hours(1);
+
+/// Where commands store their data, what time they take it to be, where they read answers to
+/// prompts from, and which lock they take while they change the data
+pub struct Context<'a> {
+    pub storage: &'a dyn Storage,
+    pub clock: &'a dyn Clock,
+    input: RefCell<Box<dyn BufRead + 'a>>,
+    lock: RefCell<Locking>,
+}
+
+/// The lock on the data of a context, which is taken by the first of any nested `Locked` guards
+/// and released with the last
+#[derive(Default)]
+struct Locking {
+    dir: Option<PathBuf>,
+    held: Option<Lock>,
+    depth: usize,
+    /// Hook commands and their events, to run once the lock is released
+    hooks: Vec<(String, Value)>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(storage: &'a dyn Storage, clock: &'a dyn Clock, input: impl BufRead + 'a) -> Self {
+        Self {
+            storage,
+            clock,
+            input: RefCell::new(Box::new(input)),
+            lock: RefCell::default(),
+        }
+    }
+
+    /// Makes commands lock the data in `dir` while they change it, so that track commands run by
+    /// other processes at the same time do not interleave their reads and writes with them
+    pub fn locking(self, dir: PathBuf) -> Self {
+        self.lock.borrow_mut().dir = Some(dir);
+        self
+    }
+
+    /// Waits until no other process is using the data, and keeps others from using it until the
+    /// returned guard is dropped. The lock may be taken again while it is held
+    pub fn lock(&self) -> Result<Locked<'_, 'a>> {
+        let mut lock = self.lock.borrow_mut();
+        if lock.depth == 0 {
+            if let Some(dir) = &lock.dir {
+                lock.held = Some(Lock::acquire(dir)?);
+            }
+        }
+        lock.depth += 1;
+        Ok(Locked { ctx: self })
+    }
+
+    /// Like `lock`, but waits at most `timeout`, returning `None` if the data is still in use
+    pub fn try_lock(&self, timeout: std::time::Duration) -> Result<Option<Locked<'_, 'a>>> {
+        let mut lock = self.lock.borrow_mut();
+        if lock.depth == 0 {
+            if let Some(dir) = &lock.dir {
+                let Some(held) = Lock::try_acquire(dir, timeout)? else {
+                    return Ok(None);
+                };
+                lock.held = Some(held);
+            }
+        }
+        lock.depth += 1;
+        Ok(Some(Locked { ctx: self }))
+    }
+}
+
+/// The lock on the data of a context, held until dropped
+pub struct Locked<'c, 'a> {
+    ctx: &'c Context<'a>,
+}
+
+impl Drop for Locked<'_, '_> {
+    fn drop(&mut self) {
+        let hooks = {
+            let mut lock = self.ctx.lock.borrow_mut();
+            lock.depth -= 1;
+            if lock.depth > 0 {
+                return;
+            }
+            lock.held = None;
+            mem::take(&mut lock.hooks)
+        };
+        for (command, event) in hooks {
+            run_hook(&command, &event);
+        }
+    }
+}
+
+pub fn create(ctx: &Context, name: String) -> Result<()> {
+    create_activity(ctx, &name)?;
+    println!("Created new activity \"{name}\"");
+    println!("\"{name}\" is now active");
+    Ok(())
+}
+
+/// Creates activity `name` and makes it active, without printing anything
+pub fn create_activity(ctx: &Context, name: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &data.activities {
+        if info.name == name {
+            bail!("error: An activity with this name already exists");
+        }
+    }
+    let id = data.next_id();
+    data.activities
+        .push(ActivityInfo::new(name.to_string(), id));
+    data.active = Some(ActivityInfo::new(name.to_string(), id));
+    data.write(ctx.storage)?;
+    Activity::new().write(ctx.storage, id)?;
+    hook(ctx, "create", name, json!({}));
+    Ok(())
+}
+
+pub fn set(ctx: &Context, name: String) -> Result<()> {
+    set_active(ctx, &name)?;
+    println!("\"{name}\" is now active");
+    Ok(())
+}
+
+/// Makes activity `name` active, without printing anything
+pub fn set_active(ctx: &Context, name: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &data.activities {
+        if info.name == name {
+            if data.is_archived(info.id) {
+                bail!("error: This activity is archived");
+            }
+            data.active = Some(info.clone());
+            data.write(ctx.storage)?;
+            return Ok(());
+        }
+    }
+    bail!("error: No activity with this name exists");
+}
+
+pub fn rename(ctx: &Context, from: String, to: String) -> Result<()> {
+    rename_activity(ctx, &from, &to)?;
+    println!("Renamed activity \"{from}\" to \"{to}\"");
+    Ok(())
+}
+
+/// Renames activity `from` to `to`, without printing anything
+pub fn rename_activity(ctx: &Context, from: &str, to: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &mut data.activities {
+        if info.name == from {
+            info.name = to.to_string();
+            if let Some(current) = &data.active {
+                if current.id == info.id {
+                    data.active = Some(info.clone());
+                }
+            }
+            data.write(ctx.storage)?;
+            hook(ctx, "rename", to, json!({ "from": from }));
+            return Ok(());
+        }
+    }
+    bail!("error: No activity with this name exists")
+}
+
+pub fn delete(ctx: &Context, name: String) -> Result<()> {
+    // Deleting by name after the answer is safe, so the data is not checked for changes
+    let data = Data::read(ctx.storage)?;
+    if !data.activities.iter().any(|info| info.name == name) {
+        bail!("error: No activity with this name exists");
+    }
+    if confirm(
+        ctx,
+        &format!("Are you sure you want to delete activity \"{name}\"?"),
+    )? {
+        delete_activity(ctx, &name)?;
+        println!("Deleted activity \"{name}\"");
+    } else {
+        println!("Did not delete activity \"{name}\"");
+    }
+    Ok(())
+}
+
+/// Deletes activity `name` without asking, and without printing anything
+pub fn delete_activity(ctx: &Context, name: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for (i, info) in data.activities.iter().enumerate() {
+        if info.name == name {
+            data.delete(ctx.storage, i)?;
+            hook(ctx, "delete", name, json!({}));
+            return Ok(());
+        }
+    }
+    bail!("error: No activity with this name exists");
+}
+
+pub fn archive(ctx: &Context, name: String) -> Result<()> {
+    archive_activity(ctx, &name)?;
+    println!("Archived activity \"{name}\"");
+    Ok(())
+}
+
+/// Archives activity `name`, without printing anything
+pub fn archive_activity(ctx: &Context, name: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &data.activities {
+        if info.name == name {
+            if data.is_archived(info.id) {
+                bail!("error: This activity is already archived");
+            }
+            if Activity::read(ctx.storage, info.id)?.ongoing.is_some() {
+                bail!("error: There is an ongoing session of \"{name}\"");
+            }
+            data.archived.push(info.id);
+            if let Some(current) = &data.active {
+                if current.id == info.id {
+                    data.active = None;
+                }
+            }
+            data.write(ctx.storage)?;
+            return Ok(());
+        }
+    }
+    bail!("error: No activity with this name exists");
+}
+
+pub fn unarchive(ctx: &Context, name: String) -> Result<()> {
+    unarchive_activity(ctx, &name)?;
+    println!("Unarchived activity \"{name}\"");
+    Ok(())
+}
+
+/// Unarchives activity `name`, without printing anything
+pub fn unarchive_activity(ctx: &Context, name: &str) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &data.activities {
+        if info.name == name {
+            if !data.is_archived(info.id) {
+                bail!("error: This activity is not archived");
+            }
+            let id = info.id;
+            data.archived.retain(|&other| other != id);
+            data.write(ctx.storage)?;
+            return Ok(());
+        }
+    }
+    bail!("error: No activity with this name exists");
+}
+
+pub fn merge(ctx: &Context, from: String, into: String) -> Result<()> {
+    if from == into {
+        bail!("error: Cannot merge an activity into itself");
+    }
+    let read = || -> Result<_> {
+        let data = Data::read(ctx.storage)?;
+        let from_info = find(&data, &from)?.clone();
+        let into_info = find(&data, &into)?.clone();
+        check_archived(&data, &into_info)?;
+        let source = Activity::read(ctx.storage, from_info.id)?;
+        let target = Activity::read(ctx.storage, into_info.id)?;
+        check_ongoing(&source, &from)?;
+        Ok((data, from_info, into_info, source, target))
+    };
+    let before = read()?;
+    if !confirm(
+        ctx,
+        &format!("Are you sure you want to merge \"{from}\" into \"{into}\"?"),
+    )? {
+        println!("Did not merge \"{from}\" into \"{into}\"");
+        return Ok(());
+    }
+    let _lock = ctx.lock()?;
+    let (mut data, from_info, into_info, mut source, mut target) = read()?;
+    check_unchanged(&before.0, &data)?;
+    check_unchanged(&(&before.3, &before.4), &(&source, &target))?;
+    let others = others(ctx, &data, &[from_info.id, into_info.id])?;
+    let total = source.sessions.len();
+    let mut conflicts = Vec::new();
+    for session in source.sessions.drain(..) {
+        let result = check_others(&others, session.start, session.end)
+            .and_then(|()| target.insert(session.clone(), ctx.clock.now()));
+        if let Err(e) = result {
+            println!("Could not move session {session}");
+            println!("{e}");
+            conflicts.push(session);
+        }
+    }
+    source.sessions = conflicts;
+    target.write(ctx.storage, into_info.id)?;
+    println!(
+        "Moved {} of {total} sessions from \"{from}\" into \"{into}\"",
+        total - source.sessions.len()
+    );
+    if source.sessions.is_empty() {
+        let was_active = matches!(&data.active, Some(current) if current.id == from_info.id);
+        let i = data
+            .activities
+            .iter()
+            .position(|info| info.id == from_info.id)
+            .unwrap();
+        data.delete(ctx.storage, i)?;
+        if was_active {
+            data.active = Some(into_info);
+            data.write(ctx.storage)?;
+            println!("\"{into}\" is now active");
+        }
+        println!("Deleted activity \"{from}\"");
+    } else {
+        source.write(ctx.storage, from_info.id)?;
+        let left = source.sessions.len();
+        println!(
+            "Left {left} overlapping session{} in \"{from}\"",
+            if left == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+pub fn split(ctx: &Context, name: String, filter: Filter) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let mut data = Data::read(ctx.storage)?;
+    for info in &data.activities {
+        if info.name == name {
+            bail!("error: An activity with this name already exists");
+        }
+    }
+    let (mut current, current_name) = data.read_current(ctx.storage)?;
+    let current_name = current_name.to_string();
+    let indices = current.select(&filter, ctx.clock.now())?;
+    let mut split = Activity::new();
+    for &i in indices.iter().rev() {
+        split.sessions.push(current.sessions.remove(i));
+    }
+    split.sessions.reverse();
+    let id = data.next_id();
+    // The new activity is written before the index that names it, and the sessions are only
+    // removed from the source once both are saved
+    split.write(ctx.storage, id)?;
+    data.activities.push(ActivityInfo::new(name.clone(), id));
+    data.write(ctx.storage)?;
+    data.write_current(ctx.storage, &current)?;
+    println!("Created new activity \"{name}\"");
+    println!("Moved sessions from \"{current_name}\":");
+    for k in 0..split.sessions.len() {
+        println!("{}", split.get(k));
+    }
+    Ok(())
+}
+
+pub fn config(
+    ctx: &Context,
+    key: Option<String>,
+    value: Option<String>,
+    reset: bool,
+) -> Result<()> {
+    let _lock = if reset || value.is_some() {
+        Some(ctx.lock()?)
+    } else {
+        None
+    };
+    let mut config = Config::read(ctx.storage)?;
+    let Some(key) = key else {
+        println!("The settings are:");
+        for (name, default, description) in SETTINGS {
+            println!(
+                "{name} = {} - {description}",
+                config.get(name).unwrap_or(default)
+            );
+        }
+        return Ok(());
+    };
+    let Some((_, default, _)) = SETTINGS.iter().find(|(name, ..)| *name == key) else {
+        bail!("error: No setting with this name exists");
+    };
+    if reset {
+        config.reset(&key);
+        config.write(ctx.storage)?;
+        println!("Reset \"{key}\" to {default}");
+    } else if let Some(value) = value {
+        let mut check = Config::default();
+        check.set(key.clone(), value.clone());
+        check_setting(&check, &key)?;
+        config.set(key.clone(), value.clone());
+        config.write(ctx.storage)?;
+        println!("Set \"{key}\" to {value}");
+    } else {
+        println!("{key} = {}", config.get(&key).unwrap_or(default));
+    }
+    Ok(())
+}
+
+pub fn current(ctx: &Context) -> Result<()> {
+    if let Some(info) = &Data::read(ctx.storage)?.active {
+        println!("\"{}\" is active", info.name);
+    } else {
+        println!("There is no activity currently active");
+    }
+    Ok(())
+}
+
+pub fn all(ctx: &Context, archived: bool) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let names: Vec<_> = data
+        .activities
+        .iter()
+        .filter(|info| data.is_archived(info.id) == archived)
+        .map(|info| &info.name)
+        .collect();
+    let kind = if archived { "archived" } else { "recorded" };
+    if names.is_empty() {
+        println!("There are currently no {kind} activities");
+    } else {
+        println!("The {kind} activities are:");
+        for name in names {
+            println!("{name}");
+        }
+    }
+    Ok(())
+}
+
+pub fn start(ctx: &Context) -> Result<()> {
+    let (name, start) = start_session(ctx)?;
+    print_started(&name, start);
+    Ok(())
+}
+
+/// Starts a session of the active activity, without printing anything, returning the name of the
+/// activity and when the session started
+pub fn start_session(ctx: &Context) -> Result<(String, DateTime)> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    let start = begin(ctx, &data, &mut current, name)?;
+    Ok((name.to_string(), start))
+}
+
+fn begin(ctx: &Context, data: &Data, current: &mut Activity, name: &str) -> Result<DateTime> {
+    if current.ongoing.is_some() {
+        bail!("There is already an ongoing session of \"{name}\"");
+    }
+    for (other, other_name) in others(ctx, data, &[data.active.as_ref().unwrap().id])? {
+        if other.ongoing.is_some() {
+            bail!("error: There is already an ongoing session of \"{other_name}\"");
+        }
+    }
+    let start = ctx.clock.now();
+    current.ongoing = Some(start);
+    current.notes.clear();
+    data.write_current(ctx.storage, current)?;
+    hook(ctx, "start", name, json!({ "start": start.to_rfc3339() }));
+    Ok(start)
+}
+
+fn print_started(name: &str, start: DateTime) {
+    let local = to_local(start);
+    println!(
+        "Started new session of \"{name}\" on {} at {}",
+        local.format("%d/%m/%y"),
+        local.format("%R")
+    );
+}
+
+pub fn timer(ctx: &Context, length: Duration, notes: String) -> Result<()> {
+    timed_session(ctx, length, notes)
+}
+
+pub fn pomodoro(ctx: &Context, rounds: Option<u32>, notes: String) -> Result<()> {
+    let config = Config::read(ctx.storage)?;
+    let work = setting_length(&config, "pomodoro-work")?;
+    let short_break = setting_length(&config, "pomodoro-break")?;
+    let long_break = setting_length(&config, "pomodoro-long-break")?;
+    let rounds = match rounds {
+        Some(rounds) => rounds,
+        None => setting_count(&config, "pomodoro-rounds")?,
+    };
+    for round in 1..=rounds {
+        let marker = format!("[pomodoro {round}/{rounds}]");
+        let notes = if notes.is_empty() {
+            marker
+        } else {
+            format!("{notes} {marker}")
+        };
+        timed_session(ctx, work, notes)?;
+        if round == rounds {
+            println!("Long break of {}", dur_stat(long_break));
+            countdown(ctx, "Break", long_break)?;
+        } else {
+            println!("Break of {}", dur_stat(short_break));
+            countdown(ctx, "Break", short_break)?;
+        }
+    }
+    println!("Finished {rounds} pomodoros");
+    Ok(())
+}
+
+/// Starts a session of the active activity, and ends it once `length` has passed
+fn timed_session(ctx: &Context, length: Duration, notes: String) -> Result<()> {
+    if ctx.clock.now().checked_add_signed(length).is_none() {
+        bail!("error: Length is too long");
+    }
+    let (name, start) = start_session(ctx)?;
+    print_started(&name, start);
+    countdown(ctx, "Session", length)?;
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    if current.ongoing.map(|ongoing| ongoing.timestamp()) != Some(start.timestamp()) {
+        bail!("error: The ongoing session of \"{name}\" was ended elsewhere");
+    }
+    finish(
+        ctx,
+        &data,
+        &mut current,
+        name,
+        start,
+        ctx.clock.now(),
+        notes,
+    )?;
+    print_ended(name, &current);
+    Ok(())
+}
+
+/// Blocks for `length`, displaying the time remaining every second
+fn countdown(ctx: &Context, label: &str, length: Duration) -> Result<()> {
+    let end = ctx
+        .clock
+        .now()
+        .checked_add_signed(length)
+        .ok_or_else(|| anyhow!("error: Length is too long"))?;
+    loop {
+        let remaining = end - ctx.clock.now();
+        if remaining <= Duration::zero() {
+            break;
+        }
+        let secs = (remaining.num_milliseconds() + 999) / 1000;
+        print!(
+            "\r{label}: {} remaining ",
+            clock_to_string(Duration::seconds(secs))
+        );
+        io::stdout().flush()?;
+        let wait = remaining.num_milliseconds() - (secs - 1) * 1000;
+        thread::sleep(std::time::Duration::from_millis(wait.max(1) as u64));
+    }
+    println!("\r{label}: finished            \x07");
+    Ok(())
+}
+
+pub fn end(ctx: &Context, notes: String) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    let Some(start) = current.ongoing else {
+        bail!("error: There is no ongoing session of \"{name}\"");
+    };
+    let end = end_time(ctx, &data, name, start)?;
+    let (current, name) = end_session(ctx, notes, Some(end))?;
+    print_ended(&name, &current);
+    Ok(())
+}
+
+/// Ends the ongoing session of the active activity at `end`, or now if it is `None`, without
+/// printing anything, returning the activity and its name
+pub fn end_session(
+    ctx: &Context,
+    notes: String,
+    end: Option<DateTime>,
+) -> Result<(Activity, String)> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    let Some(start) = current.ongoing else {
+        bail!("error: There is no ongoing session of \"{name}\"");
+    };
+    // An `end` chosen by the user is before any session started while they chose it
+    if end.is_some_and(|end| end < start) {
+        bail!(CHANGED);
+    }
+    let end = end.unwrap_or_else(|| ctx.clock.now());
+    finish(ctx, &data, &mut current, name, start, end, notes)?;
+    Ok((current, name.to_string()))
+}
+
+/// When to end the ongoing session of `name` that started at `start`, which is now unless it has
+/// exceeded the maximum session length and the user chooses otherwise
+fn end_time(ctx: &Context, data: &Data, name: &str, start: DateTime) -> Result<DateTime> {
+    match exceeded(ctx, start)? {
+        Some(max) => Ok(choose_end(ctx, data, name, start, max, false)?.unwrap()),
+        None => Ok(ctx.clock.now()),
+    }
+}
+
+fn finish(
+    ctx: &Context,
+    data: &Data,
+    current: &mut Activity,
+    name: &str,
+    start: DateTime,
+    end: DateTime,
+    notes: String,
+) -> Result<()> {
+    current.ongoing = None;
+    check_others(
+        &others(ctx, data, &[data.active.as_ref().unwrap().id])?,
+        start,
+        end,
+    )?;
+    let notes = join_notes(&mem::take(&mut current.notes), &notes);
+    let midnight = parse_dt(to_local(end).date_naive().and_hms_opt(0, 0, 0).unwrap());
+    let before = current.time_in_range(midnight, end);
+    current.sessions.push(Session::new(start, end, notes));
+    data.write_current(ctx.storage, current)?;
+    hook(
+        ctx,
+        "end",
+        name,
+        session_json(&current.sessions[current.last()]),
+    );
+    // The session is already written, so a setting that cannot be read only skips the event
+    let config = Config::read(ctx.storage).unwrap_or_default();
+    let goal = setting_duration(&config, "daily-goal").unwrap_or(None);
+    let today = current.time_in_range(midnight, end);
+    if let Some(goal) = goal.filter(|&goal| before < goal && today >= goal) {
+        hook(
+            ctx,
+            "goal",
+            name,
+            json!({ "goal": goal.num_seconds(), "today": today.num_seconds() }),
+        );
+    }
+    Ok(())
+}
+
+/// Prints the session that was just added to `current` by ending it
+fn print_ended(name: &str, current: &Activity) {
+    println!("Ended session of \"{name}\"");
+    println!("New session:");
+    println!("{}", current.get(current.last()));
+}
+
+/// Returns the maximum session length if the ongoing session that started at `start` has
+/// exceeded it
+fn exceeded(ctx: &Context, start: DateTime) -> Result<Option<Duration>> {
+    let max = setting_duration(&Config::read(ctx.storage)?, "max-session")?;
+    Ok(max.filter(|&max| ctx.clock.now() - start > max))
+}
+
+/// Asks when an ongoing session that has exceeded the maximum session length should end,
+/// returning `None` if it should be left ongoing
+fn choose_end(
+    ctx: &Context,
+    data: &Data,
+    name: &str,
+    start: DateTime,
+    max: Duration,
+    keep: bool,
+) -> Result<Option<DateTime>> {
+    let now = ctx.clock.now();
+    println!(
+        "Warning: The ongoing session of \"{name}\" has lasted {}, longer than the maximum session length of {}",
+        dur_to_string(now - start),
+        dur_stat(max)
+    );
+    let mut options = vec![
+        ("now".to_string(), Some(now)),
+        (
+            format!(
+                "at the maximum session length, {}",
+                time_to_string(start + max)
+            ),
+            Some(start + max),
+        ),
+    ];
+    if let Some(last) = last_activity(ctx, data, start)? {
+        options.push((
+            format!("at the last recorded activity, {}", time_to_string(last)),
+            Some(last),
+        ));
+    }
+    options.push(("at a specific time".to_string(), None));
+    let default = if keep {
+        "leave the session ongoing"
+    } else {
+        "end the session now"
+    };
+    loop {
+        for (k, (option, _)) in options.iter().enumerate() {
+            println!("{}. End the session {option}", k + 1);
+        }
+        let input = prompt(
+            ctx,
+            &format!("Enter an option, or leave empty to {default}: "),
+        )?;
+        if input.is_empty() {
+            return Ok((!keep).then_some(now));
+        }
+        match input
+            .parse::<usize>()
+            .ok()
+            .and_then(|k| options.get(k.wrapping_sub(1)))
+        {
+            Some((_, Some(end))) => return Ok(Some(*end)),
+            Some((_, None)) => {
+                let input = prompt(ctx, "End time [dd/mm/yy-HH:MM] or [HH:MM]: ")?;
+                match Absolute::parse(&input).map(|abs| parse_end(abs, start)) {
+                    Some(end) if end > start && end <= now => return Ok(Some(end)),
+                    Some(_) => {
+                        println!("The session must end after it starts, and not in the future")
+                    }
+                    None => println!("The time must be in the form [dd/mm/yy-HH:MM] or [HH:MM]"),
+                }
+            }
+            None => println!("No option with this number exists"),
+        }
+    }
+}
+
+/// The latest time after `after` that any session ended or started being tracked
+fn last_activity(ctx: &Context, data: &Data, after: DateTime) -> Result<Option<DateTime>> {
+    let mut last = None;
+    for info in &data.activities {
+        let activity = Activity::read(ctx.storage, info.id)?;
+        let ends = activity.sessions.iter().rev().map(|session| session.end);
+        for time in ends.take_while(|&end| end > after).chain(activity.ongoing) {
+            if time > after && last.is_none_or(|last| time > last) {
+                last = Some(time);
+            }
+        }
+    }
+    Ok(last)
+}
+
+pub fn cancel(ctx: &Context) -> Result<()> {
+    let name = cancel_session(ctx)?;
+    println!("Cancelled ongoing session of \"{name}\"");
+    Ok(())
+}
+
+/// Cancels the ongoing session of the active activity, without printing anything, returning the
+/// name of the activity
+pub fn cancel_session(ctx: &Context) -> Result<String> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    if let Some(start) = current.ongoing.take() {
+        current.notes.clear();
+        data.write_current(ctx.storage, &current)?;
+        hook(ctx, "cancel", name, json!({ "start": start.to_rfc3339() }));
+        return Ok(name.to_string());
+    }
+    bail!("error: There is no ongoing session of \"{name}\"");
+}
+
+/// Ends the ongoing session of the active activity, if there is one, and starts a session of
+/// activity `name`, unless one is already ongoing
+pub fn switch(ctx: &Context, name: String, notes: String) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let id = find(&data, &name)?.id;
+    let mut end = None;
+    if let Some(active) = data.active.as_ref().filter(|active| active.id != id) {
+        if let Some(start) = Activity::read(ctx.storage, active.id)?.ongoing {
+            end = Some(end_time(ctx, &data, &active.name, start)?);
+        }
+    }
+    let switched = switch_to(ctx, &name, notes, end)?;
+    if let Some((current, from)) = switched.ended {
+        print_ended(&from, &current);
+    }
+    println!("\"{name}\" is now active");
+    if let Some(start) = switched.started {
+        print_started(&name, start);
+    }
+    Ok(())
+}
+
+/// What track switch did
+pub struct Switched {
+    /// The activity whose ongoing session was ended and its name, if one was
+    pub ended: Option<(Activity, String)>,
+    /// When the session of the new activity started, if one was started
+    pub started: Option<DateTime>,
+}
+
+/// Does what track switch does, ending the ongoing session at `end`, or now if it is `None`,
+/// without printing anything
+pub fn switch_to(
+    ctx: &Context,
+    name: &str,
+    notes: String,
+    end: Option<DateTime>,
+) -> Result<Switched> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let id = find(&data, name)?.id;
+    let from = data.active.as_ref().map(|info| info.name.clone());
+    let mut ended = None;
+    if let Some(active) = &data.active {
+        if active.id != id && Activity::read(ctx.storage, active.id)?.ongoing.is_some() {
+            ended = Some(end_session(ctx, notes, end)?);
+        }
+    }
+    set_active(ctx, name)?;
+    let mut started = None;
+    if Activity::read(ctx.storage, id)?.ongoing.is_none() {
+        started = Some(start_session(ctx)?.1);
+    }
+    hook(ctx, "switch", name, json!({ "from": from }));
+    Ok(Switched { ended, started })
+}
+
+pub fn ongoing(ctx: &Context) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    if let Some(start) = current.ongoing {
+        let local = to_local(start);
+        println!(
+            "There is an ongoing session of \"{name}\" that started on {} at {}",
+            local.format("%d/%m/%y"),
+            local.format("%R")
+        );
+        println!(
+            "Current duration: {}",
+            dur_to_string(ctx.clock.now() - start)
+        );
+        if !current.notes.is_empty() {
+            println!("Notes: {}", current.notes);
+        }
+        if let Some(max) = exceeded(ctx, start)? {
+            if let Some(end) = choose_end(ctx, &data, name, start, max, true)? {
+                let notes = prompt(ctx, "Notes: ")?;
+                let (current, name) = end_session(ctx, notes, Some(end))?;
+                print_ended(&name, &current);
+            }
+        }
+    } else {
+        println!("There is no ongoing session of \"{name}\"");
+    }
+    Ok(())
+}
+
+pub fn watch(ctx: &Context) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    let Some(start) = current.ongoing else {
+        bail!("error: There is no ongoing session of \"{name}\"");
+    };
+    println!(
+        "Watching the ongoing session of \"{name}\" that started on {}",
+        time_to_string(start)
+    );
+    println!("Press [e] to end it, [c] to cancel it, [n] to add notes, or [q] to stop watching");
+    let midnight = parse_dt(
+        to_local(ctx.clock.now())
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+    );
+    let before = current.time_in_range(midnight, start.max(midnight));
+    loop {
+        terminal::enable_raw_mode()?;
+        let key = watch_keys(ctx, start, before, midnight);
+        terminal::disable_raw_mode()?;
+        println!();
+        match key? {
+            Some('e') => return end(ctx, prompt(ctx, "Notes: ")?),
+            Some('c') => return cancel(ctx),
+            Some('n') => {
+                let notes = prompt(ctx, "Notes: ")?;
+                let _lock = ctx.lock()?;
+                let data = Data::read(ctx.storage)?;
+                let (mut current, name) = data.read_current(ctx.storage)?;
+                if current.ongoing != Some(start) {
+                    bail!("error: The ongoing session of \"{name}\" was ended elsewhere");
+                }
+                current.notes = join_notes(&current.notes, &notes);
+                data.write_current(ctx.storage, &current)?;
+                println!("Added notes to the ongoing session of \"{name}\"");
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Redraws the elapsed time of the ongoing session every second, until a key is pressed that
+/// either stops watching or has an action
+fn watch_keys(
+    ctx: &Context,
+    start: DateTime,
+    before: Duration,
+    midnight: DateTime,
+) -> Result<Option<char>> {
+    loop {
+        let now = ctx.clock.now();
+        print!(
+            "\rElapsed: {} | Today: {} ",
+            clock_to_string(now - start),
+            dur_to_string(before + (now - start.max(midnight)))
+        );
+        io::stdout().flush()?;
+        let wait = 1000 - now.timestamp_subsec_millis().min(999);
+        if !event::poll(std::time::Duration::from_millis(wait as u64))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None)
+                }
+                KeyCode::Char(c @ ('e' | 'c' | 'n')) => return Ok(Some(c)),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Never fails, and only reads `Data` and the active activity, so that it is cheap enough to run
+/// from shell prompts and status bars
+pub fn status(ctx: &Context, template: String, json: bool) -> Result<()> {
+    let status = Status::read(ctx);
+    if json {
+        println!("{}", status.json());
+    } else if let (Some(info), Some(current), Some(start)) =
+        (&status.active, &status.current, status.ongoing)
+    {
+        let line = fill_template(&template, |key| {
+            Some(match key {
+                "activity" => info.name.clone(),
+                "elapsed" => clock_to_string(status.now - start),
+                "start" => to_local(start).format("%R").to_string(),
+                "today" => clock_to_string(status.today.unwrap()),
+                "notes" => current.notes.clone(),
+                _ => return None,
+            })
+        });
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// The status shown by track status --json, which is also what the API of track serve reports
+pub fn status_json(ctx: &Context) -> serde_json::Value {
+    Status::read(ctx).json()
+}
+
+/// The active activity and its ongoing session, as shown by track status
+struct Status {
+    active: Option<ActivityInfo>,
+    current: Option<Activity>,
+    ongoing: Option<DateTime>,
+    now: DateTime,
+    /// Time spent on the active activity today, including the ongoing session
+    today: Option<Duration>,
+}
+
+impl Status {
+    fn read(ctx: &Context) -> Self {
+        let active = Data::read(ctx.storage).ok().and_then(|data| data.active);
+        let current = active
+            .as_ref()
+            .and_then(|info| Activity::read(ctx.storage, info.id).ok());
+        let ongoing = current.as_ref().and_then(|current| current.ongoing);
+        let now = ctx.clock.now();
+        let midnight = parse_dt(to_local(now).date_naive().and_hms_opt(0, 0, 0).unwrap());
+        let today = current.as_ref().map(|current| {
+            let ongoing = ongoing.map_or(Duration::zero(), |start| now - start.max(midnight));
+            current.time_in_range(midnight, now) + ongoing
+        });
+        Self {
+            active,
+            current,
+            ongoing,
+            now,
+            today,
+        }
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "activity": self.active.as_ref().map(|info| &info.name),
+            "ongoing": self.ongoing.is_some(),
+            "start": self.ongoing.map(|start| start.to_rfc3339()),
+            "elapsed": self.ongoing.map(|start| (self.now - start).num_seconds()),
+            "today": self.today.map(|today| today.num_seconds()),
+            "notes": self
+                .current
+                .as_ref()
+                .filter(|_| self.ongoing.is_some())
+                .map(|current| &current.notes),
+        })
+    }
+}
+
+/// Replaces each {key} in `template` with its value, leaving unknown keys as they are
+fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let Some(close) = rest.find('}') else {
+            break;
+        };
+        match value(&rest[1..close]) {
+            Some(value) => filled.push_str(&value),
+            None => filled.push_str(&rest[..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+pub fn add(ctx: &Context, start: Absolute, end: Absolute, notes: String) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    check_ongoing(&current, name)?;
+    let start = parse_start(start, ctx.clock.now());
+    let end = parse_end(end, start);
+    check_others(
+        &others(ctx, &data, &[data.active.as_ref().unwrap().id])?,
+        start,
+        end,
+    )?;
+    let i = current.add(start, end, notes, ctx.clock.now())?;
+    data.write_current(ctx.storage, &current)?;
+    println!("Added a new session of \"{name}\":");
+    println!("{}", current.get(i));
+    Ok(())
+}
+
+pub fn past(
+    ctx: &Context,
+    weeks: u32,
+    days: u32,
+    hours: u32,
+    minutes: u32,
+    notes: String,
+) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    check_ongoing(&current, name)?;
+    if weeks == 0 && days == 0 && hours == 0 && minutes == 0 {
+        bail!("error: Duration must be positive");
+    }
+    let end = ctx.clock.now();
+    let start = end
+        .checked_sub_signed(Duration::minutes(
+            minutes as i64 + hours as i64 * 60 + days as i64 * 24 * 60 + weeks as i64 * 7 * 24 * 60,
+        ))
+        .ok_or_else(|| anyhow!("error: Duration is too long"))?;
+    check_others(
+        &others(ctx, &data, &[data.active.as_ref().unwrap().id])?,
+        start,
+        end,
+    )?;
+    let i = current.add(start, end, notes, ctx.clock.now())?;
+    data.write_current(ctx.storage, &current)?;
+    println!("Added a new session of \"{name}\":");
+    println!("{}", current.get(i));
+    Ok(())
+}
+
+pub fn edit(
+    ctx: &Context,
+    filter: Filter,
+    start: Option<Absolute>,
+    end: Option<Absolute>,
+    notes: Option<String>,
+    shift: Option<Duration>,
+) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    check_ongoing(&current, name)?;
+    let indices = current.select(&filter, ctx.clock.now())?;
+    if start.is_none() && end.is_none() && notes.is_none() && shift.is_none() {
+        bail!("error: No edits specified")
+    }
+    let bulk = indices.len() > 1;
+    if bulk {
+        if start.is_some() || end.is_some() {
+            bail!("error: Start and end can only be edited for a single session");
+        }
+        println!("The {} selected sessions of \"{name}\" are:", indices.len());
+        for &i in &indices {
+            println!("{}", current.get(i));
+        }
+        if !confirm(ctx, "Are you sure you want to edit these sessions?")? {
+            println!("Did not edit sessions");
+            return Ok(());
+        }
+    }
+    let _lock = ctx.lock()?;
+    let (data, mut current) = read_again(ctx, &data, &current)?;
+    let old_strings: Vec<_> = indices.iter().map(|&i| current.get(i)).collect();
+    let mut old = Vec::new();
+    for &i in indices.iter().rev() {
+        old.push(current.sessions.remove(i));
+    }
+    let others = if start.is_some() || end.is_some() || shift.is_some() {
+        others(ctx, &data, &[data.active.as_ref().unwrap().id])?
+    } else {
+        Vec::new()
+    };
+    let mut starts = Vec::new();
+    for session in old.into_iter().rev() {
+        let (start, end) = if let Some(shift) = shift {
+            match (
+                session.start.checked_add_signed(shift),
+                session.end.checked_add_signed(shift),
+            ) {
+                (Some(start), Some(end)) => (start, end),
+                _ => bail!("error: Sessions cannot be shifted that far"),
+            }
+        } else {
+            let start = start
+                .map(|abs| parse_start(abs, ctx.clock.now()))
+                .unwrap_or(session.start);
+            let end = end.map(|abs| parse_end(abs, start)).unwrap_or(session.end);
+            (start, end)
+        };
+        let notes = notes.clone().unwrap_or(session.notes);
+        check_others(&others, start, end)?;
+        let session = Session {
+            start,
+            end,
+            notes,
+            id: session.id,
+        };
+        current.insert(session, ctx.clock.now())?;
+        starts.push(start);
+    }
+    data.write_current(ctx.storage, &current)?;
+    let sessions = if bulk { "sessions" } else { "session" };
+    println!("Edited {sessions} of \"{name}\" from:");
+    for old_string in old_strings {
+        println!("{old_string}");
+    }
+    println!("to:");
+    for start in starts {
+        let i = current
+            .sessions
+            .iter()
+            .position(|session| session.start == start)
+            .unwrap();
+        println!("{}", current.get(i));
+    }
+    Ok(())
+}
+
+pub fn remove(ctx: &Context, filter: Filter) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    let indices = current.select(&filter, ctx.clock.now())?;
+    for &i in &indices {
+        println!("{}", current.get(i));
+    }
+    let (prompt, sessions) = if indices.len() == 1 {
+        (
+            format!("Are you sure you want to remove this session from \"{name}\"?"),
+            "session",
+        )
+    } else {
+        (
+            format!(
+                "Are you sure you want to remove these {} sessions from \"{name}\"?",
+                indices.len()
+            ),
+            "sessions",
+        )
+    };
+    if confirm(ctx, &prompt)? {
+        let _lock = ctx.lock()?;
+        let (data, mut current) = read_again(ctx, &data, &current)?;
+        for &i in indices.iter().rev() {
+            current.sessions.remove(i);
+        }
+        data.write_current(ctx.storage, &current)?;
+        println!("Removed {sessions}");
+    } else {
+        println!("Did not remove {sessions}");
+    }
+    Ok(())
+}
+
+pub fn split_session(
+    ctx: &Context,
+    pos: Position,
+    at: Absolute,
+    first: Option<String>,
+    second: Option<String>,
+) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    let i = current.parse_index(pos)?;
+    let old_string = current.get(i);
+    let session = &current.sessions[i];
+    let at = parse_dt(match at {
+        Absolute::DateTime(naive) => naive,
+        Absolute::Date(naive) => naive.and_hms_opt(0, 0, 0).unwrap(),
+        Absolute::Time(naive) => to_local(session.start).date_naive().and_time(naive),
+    });
+    if at <= session.start || at >= session.end {
+        bail!("error: Split time must be within the session");
+    }
+    let second = Session::new(
+        at,
+        session.end,
+        second.unwrap_or_else(|| session.notes.clone()),
+    );
+    let session = &mut current.sessions[i];
+    session.end = at;
+    if let Some(first) = first {
+        session.notes = first;
+    }
+    current.sessions.insert(i + 1, second);
+    data.write_current(ctx.storage, &current)?;
+    println!("Split session of \"{name}\":");
+    println!("{old_string}");
+    println!("into:");
+    println!("{}", current.get(i));
+    println!("{}", current.get(i + 1));
+    Ok(())
+}
+
+pub fn join(ctx: &Context, first: Position, second: Position, notes: Option<String>) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (current, name) = data.read_current(ctx.storage)?;
+    let i = current.parse_index(first)?;
+    let j = current.parse_index(second)?;
+    let (i, j) = (i.min(j), i.max(j));
+    if j != i + 1 {
+        bail!("error: Only adjacent sessions can be joined");
+    }
+    let gap = current.sessions[j].start - current.sessions[i].end;
+    if gap > JOIN_GAP {
+        println!("{}", current.get(i));
+        println!("{}", current.get(j));
+        if !confirm(
+            ctx,
+            &format!(
+                "These sessions of \"{name}\" are {} apart, which the joined session would \
+                 include. Are you sure you want to join them?",
+                dur_to_string(gap)
+            ),
+        )? {
+            println!("Did not join sessions of \"{name}\"");
+            return Ok(());
+        }
+    }
+    let _lock = ctx.lock()?;
+    let (data, mut current) = read_again(ctx, &data, &current)?;
+    let name = &data.active.as_ref().unwrap().name;
+    check_others(
+        &others(ctx, &data, &[data.active.as_ref().unwrap().id])?,
+        current.sessions[i].end,
+        current.sessions[j].start,
+    )?;
+    let old_strings = [current.get(i), current.get(j)];
+    let second = current.sessions.remove(j);
+    let first = &mut current.sessions[i];
+    first.end = second.end;
+    first.notes = notes.unwrap_or_else(|| join_notes(&first.notes, &second.notes));
+    data.write_current(ctx.storage, &current)?;
+    println!("Joined sessions of \"{name}\":");
+    for old_string in old_strings {
+        println!("{old_string}");
+    }
+    println!("into:");
+    println!("{}", current.get(i));
+    Ok(())
+}
+
+pub fn move_sessions(ctx: &Context, positions: Vec<Position>, to: String) -> Result<()> {
+    transfer(ctx, positions, to, false)
+}
+
+pub fn copy(ctx: &Context, positions: Vec<Position>, to: String) -> Result<()> {
+    transfer(ctx, positions, to, true)
+}
+
+fn transfer(ctx: &Context, positions: Vec<Position>, to: String, keep: bool) -> Result<()> {
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    let target_info = find(&data, &to)?;
+    if data.active.as_ref().unwrap().id == target_info.id {
+        bail!("error: Sessions are already in \"{to}\"");
+    }
+    check_archived(&data, target_info)?;
+    let mut target = Activity::read(ctx.storage, target_info.id)?;
+    let indices = current.parse_indices(&positions)?;
+    let others = others(
+        ctx,
+        &data,
+        &[data.active.as_ref().unwrap().id, target_info.id],
+    )?;
+    for &i in &indices {
+        let session = current.sessions[i].clone();
+        check_others(&others, session.start, session.end)?;
+        if keep {
+            target.add(session.start, session.end, session.notes, ctx.clock.now())?;
+        } else {
+            target.insert(session, ctx.clock.now())?;
+        }
+    }
+    let strings: Vec<_> = indices.iter().map(|&i| current.get(i)).collect();
+    if keep {
+        target.write(ctx.storage, target_info.id)?;
+    } else {
+        // The source is written first, and restored if the target cannot be written, so that a
+        // failure never leaves the sessions in both activities
+        let sessions = current.sessions.clone();
+        for &i in indices.iter().rev() {
+            current.sessions.remove(i);
+        }
+        data.write_current(ctx.storage, &current)?;
+        if let Err(e) = target.write(ctx.storage, target_info.id) {
+            current.sessions = sessions;
+            data.write_current(ctx.storage, &current)?;
+            return Err(e);
+        }
+    }
+    println!(
+        "{} sessions from \"{name}\" to \"{to}\":",
+        if keep { "Copied" } else { "Moved" }
+    );
+    for string in strings {
+        println!("{string}");
+    }
+    Ok(())
+}
+
+pub fn search(
+    ctx: &Context,
+    pattern: String,
+    regex: bool,
+    ignore_case: bool,
+    from: Bound,
+    to: Bound,
+    all: Option<bool>,
+) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let matcher = RegexBuilder::new(&if regex {
+        pattern.clone()
+    } else {
+        regex::escape(&pattern)
+    })
+    .case_insensitive(ignore_case)
+    .build()
+    .map_err(|e| anyhow!("error: Invalid pattern\n{e}"))?;
+    let activities = if let Some(include_archived) = all {
+        read_all(ctx, &data, include_archived)?
+    } else {
+        vec![data.read_current(ctx.storage)?]
+    };
+    let (from, to) = convert_bounds_all(
+        activities.iter().map(|(activity, _)| activity),
+        from,
+        to,
+        ctx.clock.now(),
+    )?;
+    let mut found = false;
+    for (activity, name) in &activities {
+        let (i, j) = activity.get_in_range(from, to);
+        let matches: Vec<_> = (i..j)
+            .filter(|&k| matcher.is_match(&activity.sessions[k].notes))
+            .collect();
+        if !matches.is_empty() {
+            println!("The matching sessions in \"{name}\" are:");
+            for k in matches {
+                println!("{}", activity.get(k));
+            }
+            found = true;
+        }
+    }
+    if !found {
+        println!("There are no sessions with notes matching \"{pattern}\"");
+    }
+    Ok(())
+}
+
+pub fn gaps(ctx: &Context, from: Bound, to: Bound, min: u32, interactive: bool) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let config = Config::read(ctx.storage)?;
+    let work_start = setting_time(&config, "work-start")?;
+    let work_end = setting_time(&config, "work-end")?;
+    let work_days = setting_weekdays(&config, "work-days")?;
+    if work_end <= work_start {
+        bail!("error: Working hours must end after they start");
+    }
+    let mut activities = Vec::new();
+    for info in &data.activities {
+        activities.push((info.clone(), Activity::read(ctx.storage, info.id)?));
+    }
+    let (from, to) = convert_bounds_all(
+        activities.iter().map(|(_, activity)| activity),
+        from,
+        to,
+        ctx.clock.now(),
+    )?;
+    let now = ctx.clock.now();
+    let mut covered = Vec::new();
+    for (_, activity) in &activities {
+        for session in &activity.sessions {
+            covered.push((session.start, session.end));
+        }
+        if let Some(start) = activity.ongoing {
+            covered.push((start, now));
+        }
+    }
+    covered.sort();
+    let mut gaps = Vec::new();
+    let mut date = to_local(from).date_naive();
+    while date <= to_local(to).date_naive() {
+        if work_days.contains(&date.weekday()) {
+            let start = parse_dt(date.and_time(work_start)).max(from);
+            let end = parse_dt(date.and_time(work_end)).min(to).min(now);
+            let mut cursor = start;
+            for &(session_start, session_end) in &covered {
+                if session_start >= end {
+                    break;
+                }
+                if session_start > cursor {
+                    gaps.push((cursor, session_start));
+                }
+                cursor = cursor.max(session_end);
+            }
+            if cursor < end {
+                gaps.push((cursor, end));
+            }
+        }
+        date = date.succ_opt().unwrap();
+    }
+    gaps.retain(|&(start, end)| end - start >= Duration::minutes(min.max(1) as i64));
+    let range = range_to_string(from, to);
+    if gaps.is_empty() {
+        println!("There is no untracked time during working hours from {range}");
+        return Ok(());
+    }
+    println!("The untracked time during working hours from {range} is:");
+    let mut total = Duration::zero();
+    for (k, &(start, end)) in gaps.iter().enumerate() {
+        println!("{}", gap_to_string(k, start, end));
+        total += end - start;
+    }
+    println!("Total untracked time: {}", dur_stat(total));
+    if interactive {
+        for (k, &(start, end)) in gaps.iter().enumerate() {
+            println!("{}", gap_to_string(k, start, end));
+            let i = loop {
+                let name = prompt(
+                    ctx,
+                    "Activity to assign this gap to (leave empty to skip): ",
+                )?;
+                if name.is_empty() {
+                    break None;
+                }
+                match activities.iter().position(|(info, _)| info.name == name) {
+                    Some(i) if data.is_archived(activities[i].0.id) => {
+                        println!("This activity is archived")
+                    }
+                    Some(i) => break Some(i),
+                    None => println!("No activity with this name exists"),
+                }
+            };
+            let Some(i) = i else {
+                continue;
+            };
+            let notes = prompt(ctx, "Notes: ")?;
+            let _lock = ctx.lock()?;
+            check_unchanged(&data, &Data::read(ctx.storage)?)?;
+            let info = &activities[i].0;
+            let mut activity = Activity::read(ctx.storage, info.id)?;
+            let result = others(ctx, &data, &[info.id])
+                .and_then(|others| check_others(&others, start, end))
+                .and_then(|()| activity.add(start, end, notes, now));
+            match result {
+                Ok(i) => {
+                    activity.write(ctx.storage, info.id)?;
+                    println!("Added a new session of \"{}\":", info.name);
+                    println!("{}", activity.get(i));
+                }
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn check(ctx: &Context) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let activities = read_all(ctx, &data, true)?;
+    let mut sessions = Vec::new();
+    for (k, (activity, _)) in activities.iter().enumerate() {
+        for (i, session) in activity.sessions.iter().enumerate() {
+            sessions.push((session.start, session.end, k, i));
+        }
+    }
+    sessions.sort();
+    let mut open: Vec<(DateTime, DateTime, usize, usize)> = Vec::new();
+    let mut count = 0;
+    for session in sessions {
+        let (start, _, k, i) = session;
+        open.retain(|&(_, end, ..)| end > start);
+        for &(_, end, other_k, other_i) in &open {
+            if other_k == k {
+                continue;
+            }
+            if count == 0 {
+                println!("The overlapping sessions across activities are:");
+            } else {
+                println!();
+            }
+            let (other, other_name) = &activities[other_k];
+            let (activity, name) = &activities[k];
+            println!("\"{other_name}\": {}", other.get(other_i));
+            println!("\"{name}\": {}", activity.get(i));
+            println!(
+                "Overlap: {}",
+                dur_stat(end.min(activity.sessions[i].end) - start)
+            );
+            count += 1;
+        }
+        open.push(session);
+    }
+    if count == 0 {
+        println!("There are no overlapping sessions across activities");
+    } else {
+        println!();
+        println!("Number of overlaps: {count}");
+    }
+    Ok(())
+}
+
+pub fn doctor(ctx: &Context, fix: bool) -> Result<()> {
+    let _lock = if fix { Some(ctx.lock()?) } else { None };
+    let mut data = Data::read(ctx.storage)
+        .map_err(|e| anyhow!("error: The activity index could not be read\n{e}"))?;
+    let mut doctor = Doctor {
+        fix,
+        found: 0,
+        fixed: 0,
+    };
+    let mut data_changed = false;
+
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    let mut k = 0;
+    while k < data.activities.len() {
+        let info = &data.activities[k];
+        if !ids.insert(info.id) {
+            let problem = format!(
+                "Activity \"{}\" has the same id as another activity",
+                info.name
+            );
+            if doctor.report(problem, true) {
+                data.activities.remove(k);
+                data_changed = true;
+                continue;
+            }
+        } else if !names.insert(info.name.clone()) {
+            let problem = format!("There is more than one activity named \"{}\"", info.name);
+            if doctor.report(problem, true) {
+                let info = &mut data.activities[k];
+                info.name = format!("{} ({})", info.name, info.id);
+                names.insert(info.name.clone());
+                data_changed = true;
+            }
+        }
+        k += 1;
+    }
+    if data.archived.iter().any(|id| !ids.contains(id)) {
+        let problem = "Archived activities include an activity that does not exist".to_string();
+        if doctor.report(problem, true) {
+            data.archived.retain(|id| ids.contains(id));
+            data_changed = true;
+        }
+    }
+    if let Some(active) = data.active.clone() {
+        match data.activities.iter().find(|info| info.id == active.id) {
+            None => {
+                let problem = format!("Active activity \"{}\" does not exist", active.name);
+                if doctor.report(problem, true) {
+                    data.active = None;
+                    data_changed = true;
+                }
+            }
+            Some(info) if info.name != active.name || data.is_archived(info.id) => {
+                let problem = format!("Active activity \"{}\" is out of date", active.name);
+                if doctor.report(problem, true) {
+                    data.active = (!data.is_archived(info.id)).then(|| info.clone());
+                    data_changed = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for info in &data.activities {
+        let name = &info.name;
+        if !Activity::exists(ctx.storage, info.id)? {
+            if doctor.report(format!("Activity file of \"{name}\" is missing"), true) {
+                Activity::new().write(ctx.storage, info.id)?;
+            }
+            continue;
+        }
+        let mut activity = match Activity::read(ctx.storage, info.id) {
+            Ok(activity) => activity,
+            Err(e) => {
+                doctor.report(
+                    format!("Activity file of \"{name}\" could not be read: {e}"),
+                    false,
+                );
+                continue;
+            }
+        };
+        if doctor.check_activity(
+            &mut activity,
+            name,
+            data.is_archived(info.id),
+            ctx.clock.now(),
+        ) {
+            activity.write(ctx.storage, info.id)?;
+        }
+    }
+
+    for id in Activity::stored_ids(ctx.storage)? {
+        if ids.contains(&id) {
+            continue;
+        }
+        match Activity::read(ctx.storage, id) {
+            Ok(activity) if !activity.sessions.is_empty() => {
+                let name = format!("recovered ({id})");
+                let problem = format!("Activity file {id} is not part of any activity");
+                if doctor.report(problem, true) {
+                    data.activities.push(ActivityInfo::new(name.clone(), id));
+                    data_changed = true;
+                    println!("  Recovered its sessions as activity \"{name}\"");
+                }
+            }
+            _ => {
+                let problem = format!(
+                    "Activity file {id} is empty or unreadable, and not part of any activity"
+                );
+                if doctor.report(problem, true) {
+                    Activity::remove(ctx.storage, id)?;
+                }
+            }
+        }
+    }
+
+    if data_changed {
+        data.write(ctx.storage)?;
+    }
+    if doctor.found == 0 {
+        println!("No problems found");
+    } else if fix {
+        println!("Fixed {} of {} problems", doctor.fixed, doctor.found);
+    } else {
+        println!(
+            "Found {} problems, run track doctor --fix to fix them",
+            doctor.found
+        );
+    }
+    Ok(())
+}
+
+struct Doctor {
+    fix: bool,
+    found: usize,
+    fixed: usize,
+}
+
+impl Doctor {
+    /// Reports a problem, returning whether it should be fixed
+    fn report(&mut self, problem: String, fixable: bool) -> bool {
+        self.found += 1;
+        let fix = self.fix && fixable;
+        if fix {
+            self.fixed += 1;
+            println!("{problem} (fixed)");
+        } else if self.fix {
+            println!("{problem} (cannot be fixed automatically)");
+        } else {
+            println!("{problem}");
+        }
+        fix
+    }
+
+    /// Checks the sessions of an activity, returning whether it was changed
+    fn check_activity(
+        &mut self,
+        activity: &mut Activity,
+        name: &str,
+        archived: bool,
+        now: DateTime,
+    ) -> bool {
+        let mut changed = false;
+        let invalid = activity
+            .sessions
+            .iter()
+            .filter(|session| session.end <= session.start)
+            .count();
+        if invalid > 0 {
+            let problem =
+                format!("\"{name}\" has {invalid} sessions that do not end after they start");
+            if self.report(problem, true) {
+                activity
+                    .sessions
+                    .retain(|session| session.end > session.start);
+                changed = true;
+            }
+        }
+        let mut sorted = activity
+            .sessions
+            .windows(2)
+            .all(|pair| pair[0].start <= pair[1].start);
+        if !sorted && self.report(format!("\"{name}\" has sessions out of order"), true) {
+            activity.sessions.sort_by_key(|session| session.start);
+            sorted = true;
+            changed = true;
+        }
+        let overlapping = activity
+            .sessions
+            .windows(2)
+            .filter(|pair| pair[1].start < pair[0].end)
+            .count();
+        if sorted && overlapping > 0 {
+            let problem = format!("\"{name}\" has {overlapping} overlapping sessions");
+            if self.report(problem, true) {
+                let mut merged: Vec<Session> = Vec::new();
+                for session in activity.sessions.drain(..) {
+                    match merged.last_mut() {
+                        Some(last) if session.start < last.end => {
+                            last.end = last.end.max(session.end);
+                            last.notes = join_notes(&last.notes, &session.notes);
+                        }
+                        _ => merged.push(session),
+                    }
+                }
+                activity.sessions = merged;
+                changed = true;
+            }
+        }
+        if let Some(ongoing) = activity.ongoing {
+            let problem = if ongoing > now {
+                Some("starts in the future")
+            } else if activity
+                .sessions
+                .last()
+                .is_some_and(|last| ongoing < last.end)
+            {
+                Some("starts before the end of the last session")
+            } else if archived {
+                Some("belongs to an archived activity")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                let local = to_local(ongoing);
+                let problem = format!(
+                    "The ongoing session of \"{name}\" that started on {} at {} {problem}",
+                    local.format("%d/%m/%y"),
+                    local.format("%R")
+                );
+                if self.report(problem, true) {
+                    activity.ongoing = None;
+                    activity.notes.clear();
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+pub fn daemon(ctx: &Context, interval: u64) -> Result<()> {
+    let config = Config::read(ctx.storage)?;
+    let source: Box<dyn IdleSource> = match (
+        setting(&config, "idle-command"),
+        setting(&config, "idle-file"),
+    ) {
+        ("", "") => bail!(
+            "error: No idle source set, set \"idle-command\" or \"idle-file\" with track config"
+        ),
+        ("", file) => Box::new(IdleFile(file.into())),
+        (command, _) => Box::new(IdleCommand(command.to_string())),
+    };
+    let Some(threshold) = setting_duration(&config, "idle-threshold")? else {
+        bail!("error: \"idle-threshold\" must not be off");
+    };
+    let pause = setting(&config, "idle-action") == "pause";
+    println!("Watching for idle time longer than {}", dur_stat(threshold));
+    let mut idle_since = None;
+    loop {
+        match check_idle(ctx, source.as_ref(), threshold, pause, idle_since) {
+            Ok(since) => idle_since = since,
+            Err(e) => println!("{e}"),
+        }
+        thread::sleep(std::time::Duration::from_secs(interval));
+    }
+}
+
+/// Checks once whether the user is idle, ending the ongoing session of the active activity when
+/// they become idle. Returns the activity and time that the user became idle at, if they still are
+fn check_idle(
+    ctx: &Context,
+    source: &dyn IdleSource,
+    threshold: Duration,
+    pause: bool,
+    idle_since: Option<(u32, DateTime)>,
+) -> Result<Option<(u32, DateTime)>> {
+    let idle = source.idle()?;
+    let now = ctx.clock.now();
+    if let Some((id, since)) = idle_since {
+        if idle >= threshold {
+            return Ok(idle_since);
+        }
+        let _lock = ctx.lock()?;
+        let resumed = now
+            .checked_sub_signed(idle)
+            .map_or(since, |resumed| resumed.max(since));
+        let mut log = IdleLog::read(ctx.storage)?;
+        if let Some(interval) = log
+            .intervals
+            .iter_mut()
+            .rev()
+            .find(|interval| interval.id == id && interval.start == since)
+        {
+            interval.end = Some(resumed);
+        }
+        log.write(ctx.storage)?;
+        println!(
+            "Active again on {}, after {} idle",
+            time_to_string(resumed),
+            dur_to_string(resumed - since)
+        );
+        let data = Data::read(ctx.storage)?;
+        let Some(info) = data.active.as_ref().filter(|info| info.id == id) else {
+            return Ok(None);
+        };
+        if pause && !data.is_archived(id) {
+            let mut activity = Activity::read(ctx.storage, id)?;
+            let others = others(ctx, &data, &[id])?;
+            // Resume only where `begin` would start a session, without overlapping another one
+            if activity.ongoing.is_none()
+                && others.iter().all(|(other, _)| other.ongoing.is_none())
+                && check_others(&others, resumed, now).is_ok()
+            {
+                activity.ongoing = Some(resumed);
+                activity.notes.clear();
+                activity.write(ctx.storage, id)?;
+                println!("Started new session of \"{}\"", info.name);
+                hook(
+                    ctx,
+                    "start",
+                    &info.name,
+                    json!({ "start": resumed.to_rfc3339() }),
+                );
+            }
+        }
+        return Ok(None);
+    }
+    if idle < threshold {
+        return Ok(None);
+    }
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let Some(info) = &data.active else {
+        return Ok(None);
+    };
+    let mut activity = Activity::read(ctx.storage, info.id)?;
+    let Some(start) = activity.ongoing else {
+        return Ok(None);
+    };
+    let since = now
+        .checked_sub_signed(idle)
+        .map_or(start, |since| since.max(start))
+        .trunc_subsecs(0);
+    if since > start {
+        check_others(&others(ctx, &data, &[info.id])?, start, since)?;
+    }
+    activity.ongoing = None;
+    let notes = mem::take(&mut activity.notes);
+    if since > start {
+        activity.sessions.push(Session::new(start, since, notes));
+    }
+    activity.write(ctx.storage, info.id)?;
+    match activity.sessions.last().filter(|_| since > start) {
+        Some(session) => hook(ctx, "end", &info.name, session_json(session)),
+        None => hook(
+            ctx,
+            "cancel",
+            &info.name,
+            json!({ "start": start.to_rfc3339() }),
+        ),
+    }
+    let mut log = IdleLog::read(ctx.storage)?;
+    log.intervals.push(Idle::new(info.id, since));
+    log.write(ctx.storage)?;
+    println!(
+        "Idle since {}, ended session of \"{}\"",
+        time_to_string(since),
+        info.name
+    );
+    Ok(Some((info.id, since)))
+}
+
+pub fn idle(ctx: &Context, clear: bool) -> Result<()> {
+    if clear {
+        let _lock = ctx.lock()?;
+        let mut log = IdleLog::read(ctx.storage)?;
+        log.intervals.clear();
+        log.write(ctx.storage)?;
+        println!("Cleared recorded idle intervals");
+        return Ok(());
+    }
+    let log = IdleLog::read(ctx.storage)?;
+    if log.intervals.is_empty() {
+        println!("There are no recorded idle intervals");
+        return Ok(());
+    }
+    let data = Data::read(ctx.storage)?;
+    println!("The recorded idle intervals are:");
+    for (k, interval) in log.intervals.iter().enumerate() {
+        let name = data
+            .activities
+            .iter()
+            .find(|info| info.id == interval.id)
+            .map_or("deleted activity", |info| info.name.as_str());
+        if let Some(end) = interval.end {
+            println!(
+                "{:3}. {} ({}) - \"{name}\"",
+                k + 1,
+                range_to_string(interval.start, end),
+                dur_to_string(end - interval.start)
+            );
+        } else {
+            println!(
+                "{:3}. since {} - \"{name}\"",
+                k + 1,
+                time_to_string(interval.start)
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn view(ctx: &Context, from: Bound, to: Bound) -> Result<()> {
+    let all = from.is_none() && to.is_none();
+    let data = Data::read(ctx.storage)?;
+    let (from, to, info) = data.current_range(ctx.storage, from, to, ctx.clock.now())?;
+    let name = &info.name;
+    let (offset, current) =
+        read_in_range(ctx.storage, info.id, from, to).map_err(|e| read_failed(name, e))?;
+    let text = format!(
+        "{}in \"{name}\"",
+        if all {
+            String::new()
+        } else {
+            let range = range_to_string(from, to);
+            format!("from {} ", range)
+        }
+    );
+    if current.sessions.is_empty() {
+        println!("There are no recorded sessions {text}");
+    } else {
+        println!("The recorded sessions {text} are:");
+        for (k, session) in current.sessions.iter().enumerate() {
+            println!("{:3}. @{} {}", offset + k + 1, session.short_id(), session);
+        }
+    }
+    Ok(())
+}
+
+pub fn stats(ctx: &Context, from: Bound, to: Bound) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let (from, to, info) = data.current_range(ctx.storage, from, to, ctx.clock.now())?;
+    let name = &info.name;
+    let range = range_to_string(from, to);
+    let duration = dur_stat(to - from);
+    let summary =
+        summary_in_range(ctx.storage, info.id, from, to).map_err(|e| read_failed(name, e))?;
+    if let Some(summary) = summary {
+        println!("The sessions statistics from {range} ({duration}) in \"{name}\" are:");
+        println!("Number of sessions: {}", summary.sessions);
+        println!("Total time: {}", dur_stat(summary.total));
+        println!("Average time per day: {}", dur_stat(summary.per_day));
+        println!("Average session length: {}", dur_stat(summary.average));
+        println!(
+            "Proportion of time spent on activity: {:.1}%",
+            summary.proportion * 100.
+        );
+    } else {
+        println!("There are no recorded sessions from {range} in \"{name}\"")
+    }
+    Ok(())
+}
+
+pub fn stats_all(ctx: &Context, from: Bound, to: Bound, include_archived: bool) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let infos: Vec<_> = data
+        .activities
+        .iter()
+        .filter(|info| include_archived || !data.is_archived(info.id))
+        .collect();
+    let ids: Vec<_> = infos.iter().map(|info| info.id).collect();
+    let Some((first, last)) = recorded_range(ctx.storage, &ids)? else {
+        bail!("There are no recorded sessions");
+    };
+    let (from, to) = convert_bounds(first, last, from, to, ctx.clock.now())?;
+    let range = range_to_string(from, to);
+    let duration = dur_stat(to - from);
+    let total = to - from;
+    let mut time = Duration::zero();
+    let mut count = 0;
+    let mut lines = Vec::new();
+    for info in infos {
+        let (sessions, activity_time) = ctx.storage.time_in_range(info.id, from, to)?;
+        if sessions == 0 {
+            continue;
+        }
+        let proportion = activity_time.num_seconds() as f64 / total.num_seconds() as f64;
+        lines.push(format!(
+            "\"{}\": {} over {} session{} ({:.1}%)",
+            info.name,
+            dur_stat(activity_time),
+            sessions,
+            if sessions == 1 { "" } else { "s" },
+            proportion * 100.
+        ));
+        time += activity_time;
+        count += sessions;
+    }
+    if count == 0 {
+        println!("There are no recorded sessions from {range} in any activity");
+    } else {
+        println!("The sessions statistics from {range} ({duration}) across all activities are:");
+        for line in lines {
+            println!("{line}");
+        }
+        let proportion = time.num_seconds() as f64 / total.num_seconds() as f64;
+        println!("Number of sessions: {count}");
+        println!("Total time: {}", dur_stat(time));
+        println!(
+            "Average time per day: {}",
+            dur_stat(Duration::seconds((proportion * 60. * 60. * 24.) as i64))
+        );
+        println!("Average session length: {}", dur_stat(time / count as i32));
+    }
+    Ok(())
+}
+
+pub fn sync(ctx: &Context, dir: PathBuf) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("error: \"{}\" is not a directory", dir.display());
+    }
+    let (canonical, local) = (
+        dir.canonicalize()?,
+        storage::data_dir()?.canonicalize().unwrap_or_default(),
+    );
+    if canonical == local {
+        bail!("error: Cannot sync the data directory with itself");
+    }
+    let other = storage::open_dir(&dir)?;
+    let report = {
+        // Both directories are locked for the whole merge, in the same order whichever side
+        // syncs, so that syncs in both directions cannot wait for each other
+        let other_first = canonical < local;
+        let _first = other_first.then(|| Lock::acquire(&dir)).transpose()?;
+        let _lock = ctx.lock()?;
+        let _second = (!other_first).then(|| Lock::acquire(&dir)).transpose()?;
+        sync::sync(ctx.storage, &*other)?
+    };
+    println!(
+        "Received {} changes, and sent {} changes to \"{}\"",
+        report.received,
+        report.sent,
+        dir.display()
+    );
+    if !report.conflicts.is_empty() {
+        println!("Conflicting changes that may need to be resolved manually:");
+        for conflict in report.conflicts {
+            println!("  {conflict}");
+        }
+    }
+    if !report.overlaps.is_empty() {
+        println!("Overlapping sessions, which can be edited, or merged with track doctor --fix:");
+        for overlap in report.overlaps {
+            println!("  {overlap}");
+        }
+    }
+    Ok(())
+}
+
+pub fn export(
+    ctx: &Context,
+    from: Bound,
+    to: Bound,
+    all: Option<bool>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let data = Data::read(ctx.storage)?;
+    let activities = if let Some(include_archived) = all {
+        read_all(ctx, &data, include_archived)?
+    } else {
+        vec![data.read_current(ctx.storage)?]
+    };
+    let (from, to) = convert_bounds_all(
+        activities.iter().map(|(activity, _)| activity),
+        from,
+        to,
+        ctx.clock.now(),
+    )?;
+    let mut sessions = Vec::new();
+    for (activity, name) in &activities {
+        let (i, j) = activity.get_in_range(from, to);
+        sessions.extend(
+            activity.sessions[i..j]
+                .iter()
+                .map(|session| (*name, session)),
+        );
+    }
+    let calendar = ical::write(sessions.iter().copied(), ctx.clock.now());
+    if let Some(output) = output {
+        fs::write(&output, calendar)?;
+        println!(
+            "Exported {} sessions from {} to \"{}\"",
+            sessions.len(),
+            range_to_string(from, to),
+            output.display()
+        );
+    } else {
+        print!("{calendar}");
+    }
+    Ok(())
+}
+
+pub fn import(ctx: &Context, file: PathBuf) -> Result<()> {
+    let text = fs::read_to_string(&file)
+        .map_err(|e| anyhow!("error: Failed to read \"{}\"\n{e}", file.display()))?;
+    let events = ical::read(&text)?;
+    let _lock = ctx.lock()?;
+    let data = Data::read(ctx.storage)?;
+    let (mut current, name) = data.read_current(ctx.storage)?;
+    check_ongoing(&current, name)?;
+    let others = others(ctx, &data, &[data.active.as_ref().unwrap().id])?;
+    let mut added = 0;
+    let mut skipped = Vec::new();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                skipped.push(e.to_string());
+                continue;
+            }
+        };
+        let result = check_others(&others, event.start, event.end).and_then(|()| {
+            let notes = event.description.as_ref().unwrap_or(&event.summary);
+            let notes = notes.trim().to_string();
+            current.add(event.start, event.end, notes, ctx.clock.now())
+        });
+        match result {
+            Ok(_) => added += 1,
+            Err(e) => {
+                let reason = e.to_string();
+                let reason = reason.strip_prefix("error: ").unwrap_or(&reason);
+                skipped.push(format!(
+                    "\"{}\" {}: {}",
+                    event.summary,
+                    range_to_string(event.start, event.end),
+                    reason.replace('\n', "\n    ")
+                ));
+            }
+        }
+    }
+    if added > 0 {
+        data.write_current(ctx.storage, &current)?;
+    }
+    println!("Imported {added} sessions into \"{name}\"");
+    if !skipped.is_empty() {
+        println!("Skipped {} events:", skipped.len());
+        for skipped in skipped {
+            println!("  {skipped}");
+        }
+    }
+    Ok(())
+}
+
+pub fn serve(bind: String, token: Option<String>) -> Result<()> {
+    let server = Server::bind(&bind, storage::data_dir()?, token.clone())?;
+    println!("Serving the track API at http://{bind}");
+    if token.is_none() && server.addr().is_some_and(|addr| !addr.ip().is_loopback()) {
+        println!("Warning: No token was given, so anyone who can reach {bind} can change the data");
+    }
+    server.run()
+}
+
+#[cfg(feature = "sqlite")]
+pub fn migrate(to: &str) -> Result<()> {
+    use std::fs;
+
+    use crate::storage::{FsStorage, SqliteStorage, DATABASE, FILES};
+
+    let dir = storage::data_dir()?;
+    let _lock = Lock::acquire(&dir)?;
+    let database = dir.join(DATABASE);
+    let files = FsStorage::new(&dir);
+    let count = if to == "sqlite" {
+        if database.exists() {
+            bail!("error: The data is already stored in SQLite");
+        }
+        fs::create_dir_all(&dir)?;
+        let partial = dir.join(format!("{DATABASE}.partial"));
+        if partial.exists() {
+            fs::remove_file(&partial)?;
+        }
+        let count = storage::copy(&files, &SqliteStorage::open(&partial)?)?;
+        fs::rename(&partial, &database)?;
+        for name in FILES {
+            if files.read(name)?.is_some() {
+                files.remove(name)?;
+            }
+        }
+        for id in files.activity_ids()? {
+            files.remove_activity(id)?;
+        }
+        count
+    } else {
+        if !database.exists() {
+            bail!("error: The data is already stored in files");
+        }
+        let count = storage::copy(&SqliteStorage::open(&database)?, &files)?;
+        fs::remove_file(&database)?;
+        count
+    };
+    println!(
+        "Migrated {count} activit{} to {}",
+        if count == 1 { "y" } else { "ies" },
+        if to == "sqlite" { "SQLite" } else { "files" }
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn migrate(_to: &str) -> Result<()> {
+    bail!("error: This build of track does not support SQLite, build it with the sqlite feature")
+}
+
+pub fn completions(shell: &str) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .ok_or_else(|| anyhow!("error: Unsupported shell \"{shell}\""))?;
+    completer.write_registration("COMPLETE", "track", "track", "track", &mut io::stdout())?;
+    Ok(())
+}
+
+/// Names of activities for shell completion, optionally only those that are or are not archived
+pub fn activity_names(ctx: &Context, archived: Option<bool>) -> Vec<String> {
+    let Ok(data) = Data::read(ctx.storage) else {
+        return Vec::new();
+    };
+    data.activities
+        .iter()
+        .filter(|info| archived.is_none_or(|archived| data.is_archived(info.id) == archived))
+        .map(|info| info.name.clone())
+        .collect()
+}
+
+/// Positions and ids of the sessions of the active activity for shell completion, as
+/// (position, session)
+pub fn positions(ctx: &Context) -> Vec<(String, String)> {
+    let Ok(data) = Data::read(ctx.storage) else {
+        return Vec::new();
+    };
+    let Ok((current, _)) = data.read_current(ctx.storage) else {
+        return Vec::new();
+    };
+    let mut positions: Vec<_> = (0..current.sessions.len())
+        .map(|i| ((i + 1).to_string(), current.sessions[i].to_string()))
+        .collect();
+    if let Some(session) = current.sessions.last() {
+        positions.push(("last".to_string(), session.to_string()));
+    }
+    positions.extend(
+        current
+            .sessions
+            .iter()
+            .map(|session| (format!("@{}", session.short_id()), session.to_string())),
+    );
+    positions
+}
+
+pub fn tui(ctx: &Context) -> Result<()> {
+    tui::run(ctx)
+}
+
+impl Activity {
+    fn last(&self) -> usize {
+        if self.sessions.is_empty() {
+            return 0;
+        }
+        self.sessions.len() - 1
+    }
+
+    fn parse_index(&self, pos: Position) -> Result<usize> {
+        if self.sessions.is_empty() {
+            bail!("error: There are no recorded sessions of the active activity");
+        }
+        let i = match pos {
+            Position::Last => self.last(),
+            Position::Index(i) => i - 1,
+            Position::Range(..) => bail!("error: Expected a single session"),
+            Position::Id(prefix) => return self.find_id(&prefix),
+        };
+        if i >= self.sessions.len() {
+            bail!("error: No session of the active activity with this index exists")
+        }
+        Ok(i)
+    }
+
+    /// Index of the only session whose id begins with `prefix`
+    fn find_id(&self, prefix: &str) -> Result<usize> {
+        let mut matching =
+            (0..self.sessions.len()).filter(|&i| self.sessions[i].full_id().starts_with(prefix));
+        let Some(i) = matching.next() else {
+            bail!("error: No session of the active activity with this id exists")
+        };
+        if matching.next().is_some() {
+            bail!("error: More than one session of the active activity has an id beginning with \"{prefix}\"")
+        }
+        Ok(i)
+    }
+
+    fn parse_indices(&self, positions: &[Position]) -> Result<Vec<usize>> {
+        let mut indices = BTreeSet::new();
+        for pos in positions {
+            if let Position::Range(first, last) = *pos {
+                for i in first..=last {
+                    indices.insert(self.parse_index(Position::Index(i))?);
+                }
+            } else {
+                indices.insert(self.parse_index(pos.clone())?);
+            }
+        }
+        Ok(indices.into_iter().collect())
+    }
+
+    fn select(&self, filter: &Filter, now: DateTime) -> Result<Vec<usize>> {
+        if filter.is_empty() {
+            bail!("error: No sessions specified");
+        }
+        let mut indices = if filter.positions.is_empty() {
+            (0..self.sessions.len()).collect()
+        } else {
+            self.parse_indices(&filter.positions)?
+        };
+        if !filter.from.is_none() || !filter.to.is_none() {
+            let (from, to) = self.convert_bounds(filter.from, filter.to, now)?;
+            let (i, j) = self.get_in_range(from, to);
+            indices.retain(|k| (i..j).contains(k));
+        }
+        if let Some(notes) = &filter.notes {
+            indices.retain(|&k| self.sessions[k].notes.contains(notes.as_str()));
+        }
+        if indices.is_empty() {
+            bail!("error: No sessions of the active activity match the selection");
+        }
+        Ok(indices)
+    }
+}
+
+fn read_all<'a>(
+    ctx: &Context,
+    data: &'a Data,
+    include_archived: bool,
+) -> Result<Vec<(Activity, &'a str)>> {
+    let mut activities = Vec::new();
+    for info in &data.activities {
+        if include_archived || !data.is_archived(info.id) {
+            activities.push((Activity::read(ctx.storage, info.id)?, info.name.as_str()));
+        }
+    }
+    Ok(activities)
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = range_to_string(self.start, self.end);
+        let duration = dur_to_string(self.end - self.start);
+        write!(f, "{} ({})", range, duration)?;
+        if !self.notes.is_empty() {
+            write!(f, " - {}", self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+fn to_local(date_time: DateTime) -> chrono::DateTime<Local> {
+    date_time.into()
+}
+
+fn dur_to_string(duration: Duration) -> String {
+    let mut hours = duration.num_hours();
+    let mut mins = duration.num_minutes() - hours * 60;
+    let secs = duration.num_seconds() - hours * 60 * 60 - mins * 60;
+    if secs > 0 {
+        mins += 1;
+    }
+    if mins == 60 {
+        hours += 1;
+        mins = 0;
+    }
+    format!("{hours}h {mins}m")
+}
+
+fn dur_stat(duration: Duration) -> String {
+    let hours = duration.num_hours();
+    let mins = duration.num_minutes() - hours * 60;
+    let secs = duration.num_seconds() - hours * 60 * 60 - mins * 60;
+    if hours == 0 && mins == 0 {
+        format!("{secs}s")
+    } else {
+        let hm = if hours == 0 {
+            format!("{mins}m")
+        } else if mins == 0 {
+            format!("{hours}h")
+        } else {
+            format!("{hours}h {mins}m")
+        };
+        if secs == 0 {
+            hm
+        } else {
+            format!("{hm} {secs}s")
+        }
+    }
+}
+
+fn clock_to_string(duration: Duration) -> String {
+    let secs = duration.num_seconds();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn time_to_string(time: DateTime) -> String {
+    let local = to_local(time);
+    format!("{} at {}", local.format("%d/%m/%y"), local.format("%R"))
+}
+
+fn range_to_string(from: DateTime, to: DateTime) -> String {
+    let (from, to) = (to_local(from), to_local(to));
+    let to_format = if from.date_naive() == to.date_naive() {
+        "%R"
+    } else {
+        "%d/%m/%y %R"
+    };
+    format!("{} to {}", from.format("%d/%m/%y %R"), to.format(to_format),)
+}
+
+fn prompt(ctx: &Context, text: &str) -> Result<String> {
+    // Other track commands would have to wait for the answer
+    debug_assert_eq!(
+        ctx.lock.borrow().depth,
+        0,
+        "prompted while holding the lock"
+    );
+    print!("{text}");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    ctx.input.borrow_mut().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+fn confirm(ctx: &Context, text: &str) -> Result<bool> {
+    Ok(prompt(ctx, &format!("{text} Enter \"y\" if so: "))? == "y")
+}
+
+/// Fails if `before`, which was read before asking the user something, differs from `after`, which
+/// was read once the user answered, as another track command changed the data in between
+fn check_unchanged<T: PartialEq>(before: &T, after: &T) -> Result<()> {
+    if before != after {
+        bail!(CHANGED);
+    }
+    Ok(())
+}
+
+/// Reads the data and the active activity again once the lock is taken, failing if they differ from
+/// `data` and `current`, which were read before asking the user something
+fn read_again(ctx: &Context, data: &Data, current: &Activity) -> Result<(Data, Activity)> {
+    let data_now = Data::read(ctx.storage)?;
+    let (current_now, _) = data_now.read_current(ctx.storage)?;
+    check_unchanged(&(data, current), &(&data_now, &current_now))?;
+    Ok((data_now, current_now))
+}
+
+fn join_notes(first: &str, second: &str) -> String {
+    if first.is_empty() || first == second {
+        second.to_string()
+    } else if second.is_empty() {
+        first.to_string()
+    } else {
+        format!("{first}; {second}")
+    }
+}
+
+fn gap_to_string(index: usize, start: DateTime, end: DateTime) -> String {
+    format!(
+        "{:3}. {} ({})",
+        index + 1,
+        range_to_string(start, end),
+        dur_to_string(end - start)
+    )
+}
+
+fn setting<'a>(config: &'a Config, key: &str) -> &'a str {
+    config.get(key).unwrap_or_else(|| {
+        SETTINGS
+            .iter()
+            .find(|(name, ..)| *name == key)
+            .map(|(_, default, _)| *default)
+            .unwrap()
+    })
+}
+
+fn setting_time(config: &Config, key: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(setting(config, key), "%R")
+        .map_err(|_| anyhow!("error: \"{key}\" must be in the form [HH:MM]"))
+}
+
+fn setting_weekdays(config: &Config, key: &str) -> Result<Vec<Weekday>> {
+    setting(config, key)
+        .split(',')
+        .map(|day| day.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("error: \"{key}\" must be a comma-separated list of weekdays"))
+}
+
+fn check_setting(config: &Config, key: &str) -> Result<()> {
+    match key {
+        "work-start" | "work-end" => setting_time(config, key).map(drop),
+        "work-days" => setting_weekdays(config, key).map(drop),
+        "no-overlap" => setting_bool(config, key).map(drop),
+        "max-session" | "daily-goal" | "idle-threshold" => setting_duration(config, key).map(drop),
+        "pomodoro-work" | "pomodoro-break" | "pomodoro-long-break" => {
+            setting_length(config, key).map(drop)
+        }
+        "pomodoro-rounds" => setting_count(config, key).map(drop),
+        "hook-events" => setting_events(config, key).map(drop),
+        "idle-action" => match setting(config, key) {
+            "trim" | "pause" => Ok(()),
+            _ => bail!("error: \"{key}\" must be either [trim] or [pause]"),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn setting_duration(config: &Config, key: &str) -> Result<Option<Duration>> {
+    let value = setting(config, key);
+    if value == "off" {
+        return Ok(None);
+    }
+    match parse_duration(value) {
+        Some(duration) if !duration.is_zero() => Ok(Some(duration)),
+        _ => bail!("error: \"{key}\" must be either [off] or in the form [Nw][Nd][Nh][Nm]"),
+    }
+}
+
+fn setting_length(config: &Config, key: &str) -> Result<Duration> {
+    match parse_duration(setting(config, key)) {
+        Some(duration) if !duration.is_zero() => Ok(duration),
+        _ => bail!("error: \"{key}\" must be in the form [Nw][Nd][Nh][Nm]"),
+    }
+}
+
+fn setting_count(config: &Config, key: &str) -> Result<u32> {
+    match setting(config, key).parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => bail!("error: \"{key}\" must be a positive integer"),
+    }
+}
+
+fn setting_events<'a>(config: &'a Config, key: &str) -> Result<Vec<&'a str>> {
+    let events: Vec<_> = setting(config, key)
+        .split(',')
+        .map(str::trim)
+        .filter(|event| !event.is_empty())
+        .collect();
+    if let Some(event) = events.iter().find(|event| !hooks::EVENTS.contains(event)) {
+        bail!(
+            "error: \"{key}\" must be a comma-separated list of events, which \"{event}\" is not one of: {}",
+            hooks::EVENTS.join(", ")
+        );
+    }
+    Ok(events)
+}
+
+fn setting_bool(config: &Config, key: &str) -> Result<bool> {
+    setting(config, key)
+        .parse()
+        .map_err(|_| anyhow!("error: \"{key}\" must be either [true] or [false]"))
+}
+
+/// Runs "hook-command" for `event`, which happened to activity `name`, if it is one of
+/// "hook-events". The data has already been changed, so a failure is printed rather than returned
+fn hook(ctx: &Context, event: &str, name: &str, details: Value) {
+    let Ok(config) = Config::read(ctx.storage) else {
+        return;
+    };
+    let command = setting(&config, "hook-command");
+    if command.is_empty()
+        || !setting_events(&config, "hook-events").is_ok_and(|events| events.contains(&event))
+    {
+        return;
+    }
+    let mut fields = json!({
+        "event": event,
+        "activity": name,
+        "time": ctx.clock.now().to_rfc3339(),
+    });
+    if let (Some(fields), Value::Object(details)) = (fields.as_object_mut(), details) {
+        fields.extend(details);
+    }
+    let mut lock = ctx.lock.borrow_mut();
+    if lock.depth > 0 {
+        // Track commands run by the hook command would otherwise wait for the lock
+        lock.hooks.push((command.to_string(), fields));
+    } else {
+        drop(lock);
+        run_hook(command, &fields);
+    }
+}
+
+fn run_hook(command: &str, event: &Value) {
+    if let Err(e) = hooks::run(command, event) {
+        println!(
+            "Warning: The hook command failed on {}: {e}",
+            event["event"]
+        );
+    }
+}
+
+fn session_json(session: &Session) -> Value {
+    json!({
+        "id": session.full_id(),
+        "start": session.start.to_rfc3339(),
+        "end": session.end.to_rfc3339(),
+        "notes": session.notes,
+    })
+}
+
+/// Activities that new sessions must not overlap, which are all activities not in `ignore` if
+/// the "no-overlap" setting is enabled, and none otherwise
+fn others<'a>(ctx: &Context, data: &'a Data, ignore: &[u32]) -> Result<Vec<(Activity, &'a str)>> {
+    if !setting_bool(&Config::read(ctx.storage)?, "no-overlap")? {
+        return Ok(Vec::new());
+    }
+    let mut others = Vec::new();
+    for info in &data.activities {
+        if !ignore.contains(&info.id) {
+            others.push((Activity::read(ctx.storage, info.id)?, info.name.as_str()));
+        }
+    }
+    Ok(others)
+}
+
+fn check_others(others: &[(Activity, &str)], start: DateTime, end: DateTime) -> Result<()> {
+    for (other, name) in others {
+        let (i, j) = other.get_in_range(start, end);
+        if i < j {
+            bail!(
+                "error: Session overlaps existing session of \"{name}\":\n{}",
+                other.get(i)
+            );
+        }
+        if let Some(ongoing) = other.ongoing {
+            if ongoing < end {
+                bail!("error: Session overlaps the ongoing session of \"{name}\"");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the activity is archived, as sessions cannot be added to archived activities
+fn check_archived(data: &Data, info: &ActivityInfo) -> Result<()> {
+    if data.is_archived(info.id) {
+        bail!("error: \"{}\" is archived", info.name);
+    }
+    Ok(())
+}
+
+pub(crate) fn find<'a>(data: &'a Data, name: &str) -> Result<&'a ActivityInfo> {
+    data.activities
+        .iter()
+        .find(|info| info.name == name)
+        .ok_or_else(|| anyhow!("error: No activity with the name \"{name}\" exists"))
+}
+
+fn check_ongoing(current: &Activity, name: &str) -> Result<()> {
+    if let Some(ongoing) = current.ongoing {
+        let local = to_local(ongoing);
+        bail!(
+            "error: There is already an ongoing session of \"{name}\" that started on {} at {}",
+            local.format("%d/%m/%y"),
+            local.format("%R")
+        );
+    }
+    Ok(())
+}
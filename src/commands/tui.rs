@@ -0,0 +1,566 @@
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+
+use super::{
+    check_ongoing, check_others, dur_stat, others, range_to_string, to_local, Context, Locked,
+};
+use crate::{
+    data::{Activity, Data, Session},
+    parse_end, parse_start,
+    stats::convert_bounds,
+    Absolute, Bound, DateTime,
+};
+
+const HELP: &str = "[tab] switch pane  [up/down] move  [r] range  [s/e/n] edit start/end/notes  \
+                    [d] delete  [u] undo  [q] quit";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Activities,
+    Sessions,
+}
+
+#[derive(Clone, Copy)]
+enum Range {
+    All,
+    Today,
+    Week,
+    Month,
+    Year,
+}
+
+impl Range {
+    fn next(self) -> Self {
+        match self {
+            Range::All => Range::Today,
+            Range::Today => Range::Week,
+            Range::Week => Range::Month,
+            Range::Month => Range::Year,
+            Range::Year => Range::All,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Range::All => "all time",
+            Range::Today => "today",
+            Range::Week => "past week",
+            Range::Month => "past 30 days",
+            Range::Year => "past year",
+        }
+    }
+
+    fn bounds(self, now: DateTime) -> (Bound, Bound) {
+        let ago = |days| Bound::Ago {
+            weeks: 0,
+            days,
+            hours: 0,
+            minutes: 0,
+        };
+        match self {
+            Range::All => (Bound::None, Bound::None),
+            Range::Today => {
+                let today = Bound::Absolute(Absolute::Date(to_local(now).date_naive()));
+                (today, today)
+            }
+            Range::Week => (ago(7), Bound::Now),
+            Range::Month => (ago(30), Bound::Now),
+            Range::Year => (ago(365), Bound::Now),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Start,
+    End,
+    Notes,
+}
+
+struct Input {
+    field: Field,
+    buffer: String,
+}
+
+struct Tui<'a, 'b> {
+    ctx: &'a Context<'b>,
+    data: Data,
+    /// Index into `data.activities` of the selected activity
+    selected: usize,
+    activity: Activity,
+    /// Index into the visible sessions of the selected session
+    session: usize,
+    pane: Pane,
+    range: Range,
+    input: Option<Input>,
+    message: String,
+    /// Deleted sessions, as (activity id, session), most recent last
+    deleted: Vec<(u32, Session)>,
+}
+
+pub fn run(ctx: &Context) -> Result<()> {
+    let mut tui = Tui::new(ctx, Data::read(ctx.storage)?)?;
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = tui.run(&mut out);
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+impl<'a, 'b> Tui<'a, 'b> {
+    fn new(ctx: &'a Context<'b>, data: Data) -> Result<Self> {
+        let mut tui = Self {
+            ctx,
+            data,
+            selected: 0,
+            activity: Activity::new(),
+            session: 0,
+            pane: Pane::Activities,
+            range: Range::All,
+            input: None,
+            message: String::new(),
+            deleted: Vec::new(),
+        };
+        if let Some(active) = &tui.data.active {
+            let id = active.id;
+            tui.selected = tui
+                .data
+                .activities
+                .iter()
+                .position(|info| info.id == id)
+                .unwrap_or(0);
+        }
+        if tui.data.activities.is_empty() {
+            tui.message = "There are currently no recorded activities".to_string();
+        } else {
+            tui.load()?;
+        }
+        Ok(tui)
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> Result<()> {
+        loop {
+            self.draw(out)?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(());
+            }
+            if let Some(input) = &mut self.input {
+                match key.code {
+                    KeyCode::Char(c) => input.buffer.push(c),
+                    KeyCode::Backspace => {
+                        input.buffer.pop();
+                    }
+                    KeyCode::Esc => self.input = None,
+                    KeyCode::Enter => {
+                        let input = self.input.take().unwrap();
+                        self.message = match self.apply(input) {
+                            Ok(message) => message,
+                            Err(e) => error_line(&e),
+                        };
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            self.message.clear();
+            let result = match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                    self.pane = match self.pane {
+                        Pane::Activities => Pane::Sessions,
+                        Pane::Sessions => Pane::Activities,
+                    };
+                    Ok(())
+                }
+                KeyCode::Up | KeyCode::Char('k') => self.step(false),
+                KeyCode::Down | KeyCode::Char('j') => self.step(true),
+                KeyCode::Char('r') => {
+                    self.range = self.range.next();
+                    self.session = self.visible().len().saturating_sub(1);
+                    Ok(())
+                }
+                KeyCode::Char('s') => self.begin_input(Field::Start),
+                KeyCode::Char('e') => self.begin_input(Field::End),
+                KeyCode::Char('n') => self.begin_input(Field::Notes),
+                KeyCode::Char('d') => self.delete(),
+                KeyCode::Char('u') => self.undo(),
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                self.message = error_line(&e);
+            }
+        }
+    }
+
+    /// Reads the selected activity and selects its most recent visible session
+    fn load(&mut self) -> Result<()> {
+        self.activity = Activity::read(self.ctx.storage, self.data.activities[self.selected].id)?;
+        self.session = self.visible().len().saturating_sub(1);
+        Ok(())
+    }
+
+    /// Takes the lock before a change, or reads the data again and returns `None` if another track
+    /// command changed it since it was read, so that the change is not made to outdated sessions
+    fn lock(&mut self) -> Result<Option<Locked<'a, 'b>>> {
+        let lock = self.ctx.lock()?;
+        let data = Data::read(self.ctx.storage)?;
+        let id = self.id();
+        if data == self.data && Activity::read(self.ctx.storage, id)? == self.activity {
+            return Ok(Some(lock));
+        }
+        self.data = data;
+        self.selected = (self.data.activities.iter())
+            .position(|info| info.id == id)
+            .unwrap_or(0);
+        if self.data.activities.is_empty() {
+            self.activity = Activity::new();
+        } else {
+            self.load()?;
+        }
+        self.message = "The data was changed by another track command, so it was read again".into();
+        Ok(None)
+    }
+
+    fn name(&self) -> &str {
+        &self.data.activities[self.selected].name
+    }
+
+    fn id(&self) -> u32 {
+        self.data.activities[self.selected].id
+    }
+
+    fn bounds(&self) -> Option<(DateTime, DateTime)> {
+        let (first, last) = (
+            self.activity.sessions.first()?,
+            self.activity.sessions.last()?,
+        );
+        let now = self.ctx.clock.now();
+        let (from, to) = self.range.bounds(now);
+        convert_bounds(first.start, last.end, from, to, now).ok()
+    }
+
+    /// Indices of the sessions of the selected activity in the selected range
+    fn visible(&self) -> Vec<usize> {
+        match self.bounds() {
+            Some((from, to)) => {
+                let (i, j) = self.activity.get_in_range(from, to);
+                (i..j).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn step(&mut self, down: bool) -> Result<()> {
+        match self.pane {
+            Pane::Activities => {
+                let len = self.data.activities.len();
+                if len == 0 {
+                    return Ok(());
+                }
+                self.selected = if down {
+                    (self.selected + 1).min(len - 1)
+                } else {
+                    self.selected.saturating_sub(1)
+                };
+                self.load()
+            }
+            Pane::Sessions => {
+                let len = self.visible().len();
+                self.session = if down {
+                    (self.session + 1).min(len.saturating_sub(1))
+                } else {
+                    self.session.saturating_sub(1)
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.visible().get(self.session).copied()
+    }
+
+    fn begin_input(&mut self, field: Field) -> Result<()> {
+        if self.pane != Pane::Sessions {
+            return Ok(());
+        }
+        let Some(i) = self.current() else {
+            return Ok(());
+        };
+        let session = &self.activity.sessions[i];
+        let format = |time| to_local(time).format("%d/%m/%y-%R").to_string();
+        let buffer = match field {
+            Field::Start => format(session.start),
+            Field::End => format(session.end),
+            Field::Notes => session.notes.clone(),
+        };
+        self.input = Some(Input { field, buffer });
+        Ok(())
+    }
+
+    /// Replaces the selected session with the edited one, restoring it if the edit is invalid
+    fn apply(&mut self, input: Input) -> Result<String> {
+        let Some(_lock) = self.lock()? else {
+            return Ok(mem::take(&mut self.message));
+        };
+        let i = self.current().unwrap();
+        let old = self.activity.sessions.remove(i);
+        let result = self.edited(&old, input);
+        match result {
+            Ok(start) => {
+                self.activity.write(self.ctx.storage, self.id())?;
+                self.select_start(start);
+                Ok("Edited session".to_string())
+            }
+            Err(e) => {
+                self.activity.sessions.insert(i, old);
+                Err(e)
+            }
+        }
+    }
+
+    fn edited(&mut self, old: &Session, input: Input) -> Result<DateTime> {
+        let parse = |s: &str| {
+            Absolute::parse(s).ok_or_else(|| anyhow::anyhow!("error: Invalid date or time \"{s}\""))
+        };
+        let (start, end, notes) = match input.field {
+            Field::Start => (
+                parse_start(parse(&input.buffer)?, self.ctx.clock.now()),
+                old.end,
+                old.notes.clone(),
+            ),
+            Field::End => {
+                let end = parse_end(parse(&input.buffer)?, old.start);
+                (old.start, end, old.notes.clone())
+            }
+            Field::Notes => (old.start, old.end, input.buffer),
+        };
+        if !matches!(input.field, Field::Notes) {
+            check_ongoing(&self.activity, self.name())?;
+            check_others(&others(self.ctx, &self.data, &[self.id()])?, start, end)?;
+        }
+        let session = Session {
+            start,
+            end,
+            notes,
+            id: old.id,
+        };
+        self.activity.insert(session, self.ctx.clock.now())?;
+        Ok(start)
+    }
+
+    fn select_start(&mut self, start: DateTime) {
+        let visible = self.visible();
+        self.session = visible
+            .iter()
+            .position(|&i| self.activity.sessions[i].start == start)
+            .unwrap_or(visible.len().saturating_sub(1));
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        if self.pane != Pane::Sessions {
+            return Ok(());
+        }
+        let Some(i) = self.current() else {
+            return Ok(());
+        };
+        let Some(_lock) = self.lock()? else {
+            return Ok(());
+        };
+        let session = self.activity.sessions.remove(i);
+        self.activity.write(self.ctx.storage, self.id())?;
+        self.message = format!("Removed session {}, press [u] to undo", i + 1);
+        self.deleted.push((self.id(), session));
+        self.session = self.session.min(self.visible().len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        let Some((id, session)) = self.deleted.pop() else {
+            self.message = "Nothing to undo".to_string();
+            return Ok(());
+        };
+        let Some(selected) = self.data.activities.iter().position(|info| info.id == id) else {
+            self.message = "The activity of the removed session no longer exists".to_string();
+            return Ok(());
+        };
+        if selected != self.selected {
+            self.selected = selected;
+            self.load()?;
+        }
+        let Some(_lock) = self.lock()? else {
+            self.deleted.push((id, session));
+            return Ok(());
+        };
+        let start = session.start;
+        if let Err(e) = self.activity.insert(session.clone(), self.ctx.clock.now()) {
+            self.deleted.push((id, session));
+            return Err(e);
+        }
+        self.activity.write(self.ctx.storage, id)?;
+        self.select_start(start);
+        self.message = "Restored session".to_string();
+        Ok(())
+    }
+
+    fn draw(&self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let left = (width / 3).min(24);
+        let rows = height.saturating_sub(6);
+        queue!(out, terminal::Clear(ClearType::All))?;
+        line(out, 0, 0, left, "Activities", false)?;
+        if !self.data.activities.is_empty() {
+            let title = format!("Sessions of \"{}\" ({})", self.name(), self.range.name());
+            line(out, left + 1, 0, width - left - 1, &title, false)?;
+        }
+        let divider = "-".repeat(width);
+        line(out, 0, 1, width, &divider, false)?;
+
+        let offset = scroll(self.selected, rows);
+        for (row, info) in self
+            .data
+            .activities
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(rows)
+        {
+            let mut text = info.name.clone();
+            if self.data.is_archived(info.id) {
+                text.push_str(" (archived)");
+            }
+            let highlight = row == self.selected;
+            let text = format!("{}{text}", marker(highlight));
+            let reverse = highlight && self.pane == Pane::Activities;
+            line(out, 0, 2 + row - offset, left, &text, reverse)?;
+        }
+
+        let visible = self.visible();
+        let offset = scroll(self.session, rows);
+        for (row, &i) in visible.iter().enumerate().skip(offset).take(rows) {
+            let highlight = row == self.session;
+            let text = format!("{}{}", marker(highlight), self.activity.get(i));
+            let reverse = highlight && self.pane == Pane::Sessions;
+            line(
+                out,
+                left + 1,
+                2 + row - offset,
+                width - left - 1,
+                &text,
+                reverse,
+            )?;
+        }
+        if visible.is_empty() && !self.data.activities.is_empty() {
+            line(out, left + 1, 2, width - left - 1, "No sessions", false)?;
+        }
+
+        line(out, 0, height.saturating_sub(4), width, &divider, false)?;
+        line(
+            out,
+            0,
+            height.saturating_sub(3),
+            width,
+            &self.stats(),
+            false,
+        )?;
+        line(out, 0, height.saturating_sub(2), width, HELP, false)?;
+        let bottom = match &self.input {
+            Some(input) => {
+                let label = match input.field {
+                    Field::Start => "New start [dd/mm/yy-HH:MM] or [HH:MM]",
+                    Field::End => "New end [dd/mm/yy-HH:MM] or [HH:MM]",
+                    Field::Notes => "New notes",
+                };
+                format!("{label}: {}_", input.buffer)
+            }
+            None => self.message.clone(),
+        };
+        line(out, 0, height.saturating_sub(1), width, &bottom, false)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn stats(&self) -> String {
+        let Some((from, to)) = self.bounds() else {
+            return "No recorded sessions".to_string();
+        };
+        let range = range_to_string(from, to);
+        match self.activity.summary(from, to) {
+            Some(summary) => format!(
+                "{range} | Sessions: {} | Total: {} | Per day: {} | Average: {} | {:.1}%",
+                summary.sessions,
+                dur_stat(summary.total),
+                dur_stat(summary.per_day),
+                dur_stat(summary.average),
+                summary.proportion * 100.
+            ),
+            None => format!("{range} | No recorded sessions"),
+        }
+    }
+}
+
+/// Offset of the first displayed row such that `selected` is displayed
+fn scroll(selected: usize, rows: usize) -> usize {
+    if rows == 0 {
+        0
+    } else {
+        (selected + 1).saturating_sub(rows)
+    }
+}
+
+fn marker(highlight: bool) -> &'static str {
+    if highlight {
+        "> "
+    } else {
+        "  "
+    }
+}
+
+/// Prints `text` at the given position, truncated to `width` characters
+fn line(
+    out: &mut impl Write,
+    column: usize,
+    row: usize,
+    width: usize,
+    text: &str,
+    reverse: bool,
+) -> Result<()> {
+    let text: String = text.chars().take(width).collect();
+    queue!(out, cursor::MoveTo(column as u16, row as u16))?;
+    if reverse {
+        queue!(
+            out,
+            SetAttribute(Attribute::Reverse),
+            Print(text),
+            SetAttribute(Attribute::Reset)
+        )?;
+    } else {
+        queue!(out, Print(text))?;
+    }
+    Ok(())
+}
+
+/// Errors are displayed on a single line at the bottom of the screen
+fn error_line(e: &anyhow::Error) -> String {
+    e.to_string().lines().collect::<Vec<_>>().join(" ")
+}
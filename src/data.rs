@@ -0,0 +1,369 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashSet},
+    hash::{BuildHasher, Hasher},
+};
+
+use anyhow::{anyhow, bail, Result};
+use bincode::{deserialize, serialize, Options};
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use serde::{Deserialize, Serialize};
+
+use crate::{storage::Storage, DateTime};
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Data {
+    pub active: Option<ActivityInfo>,
+    pub activities: Vec<ActivityInfo>,
+    pub archived: Vec<u32>,
+}
+
+/// Layout of `Data` written before activities could be archived
+#[derive(Deserialize)]
+struct LegacyData(Option<ActivityInfo>, Vec<ActivityInfo>);
+
+impl Data {
+    pub fn read(storage: &dyn Storage) -> Result<Self> {
+        Ok(if let Ok(Some(encoded)) = storage.read("data") {
+            match deserialize(&encoded) {
+                Ok(data) => data,
+                Err(_) => {
+                    let LegacyData(active, activities) = deserialize(&encoded)?;
+                    Self {
+                        active,
+                        activities,
+                        archived: Vec::new(),
+                    }
+                }
+            }
+        } else {
+            Self {
+                active: None,
+                activities: Vec::new(),
+                archived: Vec::new(),
+            }
+        })
+    }
+
+    pub fn write(&self, storage: &dyn Storage) -> Result<()> {
+        storage.write("data", &serialize(self)?)
+    }
+
+    pub fn delete(&mut self, storage: &dyn Storage, i: usize) -> Result<()> {
+        let removed = self.activities.remove(i);
+        if let Some(current) = &self.active {
+            if current.id == removed.id {
+                self.active = None;
+            }
+        }
+        self.archived.retain(|&id| id != removed.id);
+        Activity::remove(storage, removed.id)?;
+        self.write(storage)
+    }
+
+    pub fn next_id(&self) -> u32 {
+        let taken: HashSet<_> = self.activities.iter().map(|info| info.id).collect();
+        let mut id = 0;
+        while taken.contains(&id) {
+            id += 1;
+        }
+        id
+    }
+
+    pub fn is_archived(&self, id: u32) -> bool {
+        self.archived.contains(&id)
+    }
+
+    pub fn read_current(&self, storage: &dyn Storage) -> Result<(Activity, &str)> {
+        if let Some(info) = &self.active {
+            let activity =
+                Activity::read(storage, info.id).map_err(|e| read_failed(&info.name, e))?;
+            Ok((activity, &info.name))
+        } else {
+            bail!("error: No activity currently active")
+        }
+    }
+
+    pub fn write_current(&self, storage: &dyn Storage, activity: &Activity) -> Result<()> {
+        activity.write(storage, self.active.as_ref().unwrap().id)
+    }
+}
+
+/// Error for an activity named `name` that could not be read, caused by `e`
+pub(crate) fn read_failed(name: &str, e: anyhow::Error) -> anyhow::Error {
+    let message =
+        format!("error: Failed to read \"{name}\", run track doctor to check for problems\n{e}");
+    e.context(message)
+}
+
+/// Decodes `encoded` as exactly one `T`, so that a file written with an older layout is not
+/// mistaken for a newer one
+fn decode_exact<'a, T: Deserialize<'a>>(encoded: &'a [u8]) -> bincode::Result<T> {
+    bincode::options()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(encoded)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn read(storage: &dyn Storage) -> Result<Self> {
+        Ok(if let Ok(Some(encoded)) = storage.read("config") {
+            deserialize(&encoded)?
+        } else {
+            Self::default()
+        })
+    }
+
+    pub fn write(&self, storage: &dyn Storage) -> Result<()> {
+        storage.write("config", &serialize(self)?)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn set(&mut self, key: String, value: String) {
+        self.values.insert(key, value);
+    }
+
+    pub fn reset(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+}
+
+/// Intervals that `track daemon` found the user to be idle for
+#[derive(Serialize, Deserialize, Default)]
+pub struct IdleLog {
+    pub intervals: Vec<Idle>,
+}
+
+impl IdleLog {
+    pub fn read(storage: &dyn Storage) -> Result<Self> {
+        Ok(if let Ok(Some(encoded)) = storage.read("idle") {
+            deserialize(&encoded)?
+        } else {
+            Self::default()
+        })
+    }
+
+    pub fn write(&self, storage: &dyn Storage) -> Result<()> {
+        storage.write("idle", &serialize(self)?)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Idle {
+    pub id: u32,
+    #[serde(with = "ts_seconds")]
+    pub start: DateTime,
+    #[serde(with = "ts_seconds_option")]
+    pub end: Option<DateTime>,
+}
+
+impl Idle {
+    pub fn new(id: u32, start: DateTime) -> Self {
+        Self {
+            id,
+            start,
+            end: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityInfo {
+    pub name: String,
+    pub id: u32,
+}
+
+impl ActivityInfo {
+    pub fn new(name: String, id: u32) -> Self {
+        Self { name, id }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+pub struct Activity {
+    #[serde(with = "ts_seconds_option")]
+    pub ongoing: Option<DateTime>,
+    pub sessions: Vec<Session>,
+    /// Notes for the ongoing session
+    pub notes: String,
+}
+
+/// Layout of `Activity` written before sessions had ids
+#[derive(Deserialize)]
+struct UnidentifiedActivity(
+    #[serde(with = "ts_seconds_option")] Option<DateTime>,
+    Vec<LegacySession>,
+    String,
+);
+
+/// Layout of `Activity` written before ongoing sessions could have notes
+#[derive(Deserialize)]
+struct LegacyActivity(
+    #[serde(with = "ts_seconds_option")] Option<DateTime>,
+    Vec<LegacySession>,
+);
+
+impl Activity {
+    pub fn new() -> Self {
+        Self {
+            ongoing: None,
+            sessions: Vec::new(),
+            notes: String::new(),
+        }
+    }
+
+    pub fn read(storage: &dyn Storage, id: u32) -> Result<Self> {
+        storage
+            .read_activity(id)?
+            .ok_or_else(|| anyhow!("Activity file {id} does not exist"))
+    }
+
+    pub fn write(&self, storage: &dyn Storage, id: u32) -> Result<()> {
+        storage.write_activity(id, self)
+    }
+
+    pub fn exists(storage: &dyn Storage, id: u32) -> Result<bool> {
+        Ok(storage.activity_ids()?.contains(&id))
+    }
+
+    pub fn remove(storage: &dyn Storage, id: u32) -> Result<()> {
+        storage.remove_activity(id)
+    }
+
+    /// Ids of all stored activity files, whether or not `Data` refers to them
+    pub fn stored_ids(storage: &dyn Storage) -> Result<Vec<u32>> {
+        storage.activity_ids()
+    }
+
+    /// Decodes an activity file, including files written with older layouts
+    pub fn decode(encoded: &[u8]) -> Result<Self> {
+        if let Ok(activity) = decode_exact(encoded) {
+            return Ok(activity);
+        }
+        let (ongoing, sessions, notes) = match decode_exact(encoded) {
+            Ok(UnidentifiedActivity(ongoing, sessions, notes)) => (ongoing, sessions, notes),
+            Err(_) => {
+                let LegacyActivity(ongoing, sessions) = decode_exact(encoded)?;
+                (ongoing, sessions, String::new())
+            }
+        };
+        Ok(Self {
+            ongoing,
+            sessions: sessions.into_iter().map(Session::from).collect(),
+            notes,
+        })
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(serialize(self)?)
+    }
+
+    /// Inserts a new session in order, returning its index. `now` is the current time, which the
+    /// session must not end after. Sessions may touch, so that one can end at the moment the next
+    /// starts, as the halves of a split session do
+    pub fn add(
+        &mut self,
+        start: DateTime,
+        end: DateTime,
+        notes: String,
+        now: DateTime,
+    ) -> Result<usize> {
+        self.insert(Session::new(start, end, notes), now)
+    }
+
+    /// Inserts a session in order, keeping its id, and returns its index. Sessions may touch, so
+    /// that one can end at the moment the next starts, as the halves of a split session do
+    pub fn insert(&mut self, session: Session, now: DateTime) -> Result<usize> {
+        let (start, end) = (session.start, session.end);
+        if end <= start {
+            bail!("error: Session must end after it starts");
+        }
+        if end > now {
+            bail!("error: Session cannot have ended in the future");
+        }
+        // Sessions are in order and do not overlap, so the ends are in order too
+        let i = self.sessions.partition_point(|other| other.start < end);
+        let k = self.sessions[..i].partition_point(|other| other.end <= start);
+        if k < i {
+            bail!("error: Session overlaps existing session:\n{}", self.get(k));
+        }
+        self.sessions.insert(i, session);
+        Ok(i)
+    }
+
+    /// The session at `index`, as shown in track view
+    pub fn get(&self, index: usize) -> String {
+        let session = &self.sessions[index];
+        format!("{:3}. @{} {}", index + 1, session.short_id(), session)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Session {
+    #[serde(with = "ts_seconds")]
+    pub start: DateTime,
+    #[serde(with = "ts_seconds")]
+    pub end: DateTime,
+    pub notes: String,
+    /// Identifies the session across edits, and across machines when syncing
+    pub id: u64,
+}
+
+/// Layout of `Session` written before sessions had ids
+#[derive(Deserialize)]
+struct LegacySession(
+    #[serde(with = "ts_seconds")] DateTime,
+    #[serde(with = "ts_seconds")] DateTime,
+    String,
+);
+
+impl From<LegacySession> for Session {
+    fn from(LegacySession(start, end, notes): LegacySession) -> Self {
+        Self {
+            id: Session::derived_id(start.timestamp(), end.timestamp(), &notes),
+            start,
+            end,
+            notes,
+        }
+    }
+}
+
+impl Session {
+    /// A session with a new random id
+    pub fn new(start: DateTime, end: DateTime, notes: String) -> Self {
+        Self {
+            start,
+            end,
+            notes,
+            id: RandomState::new().build_hasher().finish(),
+        }
+    }
+
+    /// The id in full, as accepted by `Position::Id`
+    pub fn full_id(&self) -> String {
+        format!("{:016x}", self.id)
+    }
+
+    /// The beginning of the id, as shown in track view
+    pub fn short_id(&self) -> String {
+        self.full_id()[..8].to_string()
+    }
+
+    /// The id of a session written before sessions had ids, derived from its contents so that it
+    /// is the same every time the session is read, and on every machine with a copy of it
+    pub(crate) fn derived_id(start: i64, end: i64, notes: &str) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let (start, end) = (start.to_le_bytes(), end.to_le_bytes());
+        for &byte in start.iter().chain(&end).chain(notes.as_bytes()) {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
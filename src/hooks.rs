@@ -0,0 +1,50 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// Events that can run the hook command
+pub const EVENTS: [&str; 8] = [
+    "start", "end", "cancel", "switch", "create", "rename", "delete", "goal",
+];
+
+/// Runs a shell command, giving it `event` as JSON on stdin, and each of its fields that is a
+/// string or number as an environment variable, e.g. "activity" as TRACK_ACTIVITY
+pub fn run(command: &str, event: &Value) -> Result<()> {
+    let mut process = Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    if let Some(fields) = event.as_object() {
+        for (key, value) in fields {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Number(value) => value.to_string(),
+                _ => continue,
+            };
+            process.env(format!("TRACK_{}", key.to_uppercase()), value);
+        }
+    }
+    let mut child = process.spawn()?;
+    // The hook may exit without reading its input, which is not a failure
+    let _ = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(event.to_string().as_bytes());
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.trim() {
+            "" => bail!("{}", output.status),
+            stderr => bail!("{}\n{stderr}", output.status),
+        }
+    }
+    Ok(())
+}
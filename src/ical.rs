@@ -0,0 +1,215 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{data::Session, parse_dt, DateTime};
+
+/// A VEVENT read from an iCalendar file
+pub struct Event {
+    pub start: DateTime,
+    pub end: DateTime,
+    pub summary: String,
+    /// None when the event has no DESCRIPTION
+    pub description: Option<String>,
+}
+
+/// An iCalendar file with a VEVENT for each of `sessions`, as (activity name, session). The UID of
+/// each event is the id of its session, so exporting a session again updates the same event
+pub fn write<'a>(
+    sessions: impl IntoIterator<Item = (&'a str, &'a Session)>,
+    now: DateTime,
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//track//track {}//EN", env!("CARGO_PKG_VERSION")),
+    ];
+    for (name, session) in sessions {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@track", session.full_id()));
+        lines.push(format!("DTSTAMP:{}", format_time(now)));
+        lines.push(format!("DTSTART:{}", format_time(session.start)));
+        lines.push(format!("DTEND:{}", format_time(session.end)));
+        lines.push(format!("SUMMARY:{}", escape(name)));
+        if !session.notes.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&session.notes)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// The VEVENTs of an iCalendar file, each of which is an error if it cannot be a session, e.g.
+/// because it lasts all day
+pub fn read(text: &str) -> Result<Vec<Result<Event>>> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let first = lines
+        .first()
+        .map(|line| line.trim_start_matches('\u{feff}').trim());
+    if first != Some("BEGIN:VCALENDAR") {
+        bail!("error: The file is not an iCalendar file");
+    }
+    let mut events = Vec::new();
+    // The properties of the event being read, and how many components such as alarms within it
+    // are being skipped
+    let mut properties = None;
+    let mut nested = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.split(';').next().unwrap().to_ascii_uppercase();
+        match (name.as_str(), value.trim(), &mut properties) {
+            ("BEGIN", "VEVENT", None) => properties = Some(Vec::new()),
+            ("END", "VEVENT", Some(_)) if nested == 0 => {
+                events.push(event(&properties.take().unwrap()));
+            }
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", _, Some(_)) => nested -= 1,
+            (_, _, Some(properties)) if nested == 0 => {
+                properties.push((name, value.to_string()));
+            }
+            _ => (),
+        }
+    }
+    Ok(events)
+}
+
+/// The event with `properties`, as (name, value)
+fn event(properties: &[(String, String)]) -> Result<Event> {
+    let get = |key: &str| {
+        properties
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    let summary = get("SUMMARY").map_or(String::new(), unescape);
+    let description = get("DESCRIPTION").map(unescape);
+    let Some(start) = get("DTSTART") else {
+        bail!("\"{summary}\" has no start");
+    };
+    let start = parse_time(start).map_err(|e| anyhow!("\"{summary}\" {e}"))?;
+    let end = match (get("DTEND"), get("DURATION")) {
+        (Some(end), _) => parse_time(end),
+        (None, Some(duration)) => parse_ical_duration(duration).and_then(|duration| {
+            start
+                .checked_add_signed(duration)
+                .ok_or_else(|| anyhow!("ends too late"))
+        }),
+        (None, None) => Err(anyhow!("has no end")),
+    }
+    .map_err(|e| anyhow!("\"{summary}\" {e}"))?;
+    Ok(Event {
+        start,
+        end,
+        summary,
+        description,
+    })
+}
+
+/// Parses a DATE-TIME, in UTC if it ends with Z and in local time otherwise, including when it
+/// names a time zone, which is assumed to be the local one
+fn parse_time(value: &str) -> Result<DateTime> {
+    let value = value.trim();
+    if NaiveDate::parse_from_str(value, "%Y%m%d").is_ok() {
+        bail!("lasts all day");
+    }
+    let (naive, utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let naive = NaiveDateTime::parse_from_str(naive, "%Y%m%dT%H%M%S")
+        .map_err(|_| anyhow!("has a time that is not in the form YYYYMMDDTHHMMSS: {value}"))?;
+    Ok(if utc {
+        Utc.from_utc_datetime(&naive)
+    } else {
+        parse_dt(naive)
+    })
+}
+
+/// The longest DURATION accepted, well within what a Duration and a DateTime can hold
+const MAX_DURATION_SECONDS: i64 = 100 * 366 * 24 * 60 * 60;
+
+/// Parses a DURATION such as PT1H30M
+fn parse_ical_duration(value: &str) -> Result<Duration> {
+    let invalid = || anyhow!("has a duration that is not in the form P[nW][nD][T[nH][nM][nS]]");
+    let mut rest = value.trim().strip_prefix('P').ok_or_else(invalid)?;
+    let mut seconds: i64 = 0;
+    let mut time = false;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            time = true;
+            rest = after;
+            continue;
+        }
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let n: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = rest[digits..].chars().next().unwrap();
+        let unit_seconds = match (time, unit) {
+            (false, 'W') => 7 * 24 * 60 * 60,
+            (false, 'D') => 24 * 60 * 60,
+            (true, 'H') => 60 * 60,
+            (true, 'M') => 60,
+            (true, 'S') => 1,
+            _ => return Err(invalid()),
+        };
+        seconds = n
+            .checked_mul(unit_seconds)
+            .and_then(|n| seconds.checked_add(n))
+            .filter(|&seconds| seconds <= MAX_DURATION_SECONDS)
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+    Ok(Duration::seconds(seconds))
+}
+
+fn format_time(time: DateTime) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a line into lines of at most 75 bytes, where each continuation starts with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
@@ -0,0 +1,56 @@
+use std::{fs, path::PathBuf, process::Command, time::SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Duration;
+
+/// A source of how long the user has been idle for, watched by `track daemon`
+pub trait IdleSource {
+    fn idle(&self) -> Result<Duration>;
+}
+
+/// The longest idle time read from an idle command, so that it can be subtracted from any time
+const MAX_IDLE_SECONDS: f64 = 100. * 366. * 24. * 60. * 60.;
+
+/// A shell command that prints the number of seconds the user has been idle for
+pub struct IdleCommand(pub String);
+
+impl IdleSource for IdleCommand {
+    fn idle(&self) -> Result<Duration> {
+        let output = Command::new("sh").arg("-c").arg(&self.0).output()?;
+        if !output.status.success() {
+            bail!(
+                "error: Idle command failed\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let secs: f64 = stdout.trim().parse().map_err(|_| {
+            anyhow!(
+                "error: Idle command must print a number of seconds, but printed \"{}\"",
+                stdout.trim()
+            )
+        })?;
+        if !secs.is_finite() || secs < 0. {
+            bail!(
+                "error: Idle command must print a number of seconds, but printed \"{}\"",
+                stdout.trim()
+            );
+        }
+        Ok(Duration::milliseconds(
+            (secs.min(MAX_IDLE_SECONDS) * 1000.) as i64,
+        ))
+    }
+}
+
+/// A file that is touched whenever the user is active, e.g. by a shell prompt hook
+pub struct IdleFile(pub PathBuf);
+
+impl IdleSource for IdleFile {
+    fn idle(&self) -> Result<Duration> {
+        let modified = fs::metadata(&self.0)?.modified()?;
+        let elapsed = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        Ok(Duration::from_std(elapsed)?)
+    }
+}
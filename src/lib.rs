@@ -0,0 +1,128 @@
+pub mod clock;
+pub mod commands;
+pub mod data;
+pub mod hooks;
+pub mod ical;
+mod idle;
+pub mod serve;
+pub mod stats;
+pub mod storage;
+pub mod sync;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+pub type DateTime = chrono::DateTime<chrono::Utc>;
+
+#[derive(Clone)]
+pub enum Position {
+    Index(usize),
+    Range(usize, usize),
+    Last,
+    /// A session id, or the beginning of one, in lowercase hexadecimal
+    Id(String),
+}
+
+#[derive(Clone, Copy)]
+pub enum Absolute {
+    DateTime(NaiveDateTime),
+    Date(NaiveDate),
+    Time(NaiveTime),
+}
+
+impl Absolute {
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(s, "%d/%m/%y-%R") {
+            Some(Absolute::DateTime(date_time))
+        } else if let Ok(date) = NaiveDate::parse_from_str(s, "%d/%m/%y") {
+            Some(Absolute::Date(date))
+        } else if let Ok(time) = NaiveTime::parse_from_str(s, "%R") {
+            Some(Absolute::Time(time))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Bound {
+    None,
+    Now,
+    Absolute(Absolute),
+    Ago {
+        weeks: u32,
+        days: u32,
+        hours: u32,
+        minutes: u32,
+    },
+}
+
+impl Bound {
+    fn is_none(&self) -> bool {
+        matches!(self, Bound::None)
+    }
+}
+
+pub struct Filter {
+    pub positions: Vec<Position>,
+    pub from: Bound,
+    pub to: Bound,
+    pub notes: Option<String>,
+}
+
+impl Filter {
+    fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.notes.is_none()
+    }
+}
+
+/// Parses a duration in the form [Nw][Nd][Nh][Nm], e.g. 1h30m, or returns `None` if it is not
+/// in that form or is too long for a `Duration`
+pub fn parse_duration(mut s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut minutes: i64 = 0;
+    while !s.is_empty() {
+        let digits = s.find(|c: char| !c.is_ascii_digit())?;
+        let n: i64 = s[..digits].parse().ok()?;
+        let mut rest = s[digits..].chars();
+        let unit = match rest.next()? {
+            'w' => 7 * 24 * 60,
+            'd' => 24 * 60,
+            'h' => 60,
+            'm' => 1,
+            _ => return None,
+        };
+        minutes = n.checked_mul(unit)?.checked_add(minutes)?;
+        s = rest.as_str();
+    }
+    Duration::try_minutes(minutes)
+}
+
+/// Converts a local date and time to UTC
+pub fn parse_dt(naive: NaiveDateTime) -> DateTime {
+    Local.from_local_datetime(&naive).unwrap().into()
+}
+
+/// Resolves `abs` as the start of a range, where a date means the start of that day and a time is
+/// on the date of `now`
+pub fn parse_start(abs: Absolute, now: DateTime) -> DateTime {
+    parse_dt(match abs {
+        Absolute::DateTime(naive) => naive,
+        Absolute::Date(naive) => naive.and_hms_opt(0, 0, 0).unwrap(),
+        Absolute::Time(naive) => now.with_timezone(&Local).date_naive().and_time(naive),
+    })
+}
+
+/// Resolves `abs` as the end of a range that begins at `start`, where a date means the end of
+/// that day and a time is on the date of `start`
+pub fn parse_end(abs: Absolute, start: DateTime) -> DateTime {
+    parse_dt(match abs {
+        Absolute::DateTime(naive) => naive,
+        Absolute::Date(naive) => naive.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        Absolute::Time(naive) => start.with_timezone(&Local).date_naive().and_time(naive),
+    })
+}
@@ -1,9 +1,15 @@
-mod track;
+use std::{io, path::PathBuf};
 
 use anyhow::Result;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use clap::{Parser, Subcommand};
-use track::{commands, Absolute, Bound, Position};
+use chrono::{Duration, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{engine::ArgValueCandidates, env::CompleteEnv, CompletionCandidate};
+use track::{
+    clock::SystemClock,
+    commands::{self, Context},
+    storage::{self, MemoryStorage},
+    Absolute, Bound, Filter, Position,
+};
 
 #[derive(Parser)]
 #[clap(about)]
@@ -24,13 +30,13 @@ enum Command {
     /// Set the active activity that other commands should act on
     Set {
         /// Name of the activity
-        #[arg(value_parser = parse_name)]
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(unarchived_names))]
         name: String,
     },
     /// Rename an activity
     Rename {
         /// Name of the activity to rename
-        #[arg(value_parser = parse_name)]
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
         from: String,
         /// New name
         #[arg(value_parser = parse_name)]
@@ -39,13 +45,62 @@ enum Command {
     /// Delete an activity
     Delete {
         /// Name of the activity to delete
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
+        name: String,
+    },
+    /// Archive an activity, hiding it while keeping its history
+    Archive {
+        /// Name of the activity to archive
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(unarchived_names))]
+        name: String,
+    },
+    /// Unarchive an archived activity
+    Unarchive {
+        /// Name of the activity to unarchive
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(archived_names))]
+        name: String,
+    },
+    /// Move all sessions of one activity into another, then delete it
+    Merge {
+        /// Name of the activity to merge
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
+        from: String,
+        /// Name of the activity to merge into
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
+        into: String,
+    },
+    #[clap(
+        about = "Move matching sessions of the active activity into a new activity",
+        long_about = SPLIT_ABOUT
+    )]
+    Split {
+        /// Name for the new activity
         #[arg(value_parser = parse_name)]
         name: String,
+        /// Only move sessions whose notes contain this text
+        #[arg(short, value_parser = parse_notes)]
+        matching: Option<String>,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
+    },
+    /// Display or change settings
+    Config {
+        /// Name of the setting
+        key: Option<String>,
+        /// New value for the setting
+        value: Option<String>,
+        /// Reset the setting to its default value
+        #[arg(long, requires = "key", conflicts_with = "value")]
+        reset: bool,
     },
     /// Display the name of the active activity
     Active,
     /// Display the names of all tracked activities
-    All,
+    All {
+        /// Display archived activities instead
+        #[arg(long)]
+        archived: bool,
+    },
     /// Start tracking a session
     Start,
     /// End tracking of the ongoing session
@@ -56,8 +111,54 @@ enum Command {
     },
     /// Cancel tracking of the ongoing session
     Cancel,
+    /// End the ongoing session, if there is one, and start tracking a session of another activity
+    Switch {
+        /// Name of the activity to switch to
+        #[arg(value_parser = parse_name, add = ArgValueCandidates::new(unarchived_names))]
+        name: String,
+        /// Optional notes for the ended session
+        #[arg(short, value_parser = parse_notes, default_value_t = String::new(), hide_default_value = true)]
+        notes: String,
+    },
+    #[clap(
+        about = "Track a session for a specific length of time",
+        long_about = TIMER_ABOUT)]
+    Timer {
+        /// Length of the session [Nw][Nd][Nh][Nm]
+        #[arg(value_parser = parse_length)]
+        length: Duration,
+        /// Optional notes
+        #[arg(short, value_parser = parse_notes, default_value_t = String::new(), hide_default_value = true)]
+        notes: String,
+    },
+    #[clap(
+        about = "Track sessions with breaks using the Pomodoro technique",
+        long_about = POMODORO_ABOUT)]
+    Pomodoro {
+        /// Number of sessions, instead of "pomodoro-rounds"
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        rounds: Option<u32>,
+        /// Optional notes
+        #[arg(short, value_parser = parse_notes, default_value_t = String::new(), hide_default_value = true)]
+        notes: String,
+    },
     /// Display details of the ongoing session
-    Ongoing,
+    Ongoing {
+        /// Keep displaying the elapsed time, with keys to end, cancel or add notes to the session
+        #[arg(short, long)]
+        watch: bool,
+    },
+    #[clap(
+        about = "Display the ongoing session briefly, for shell prompts and status bars",
+        long_about = STATUS_ABOUT)]
+    Status {
+        /// Format of the output
+        #[arg(short, long, default_value = "{activity} {elapsed}")]
+        template: String,
+        /// Output JSON instead, including when there is no ongoing session
+        #[arg(long, conflicts_with = "template")]
+        json: bool,
+    },
     #[clap(
         about = "Add a new session",
         long_about = ADD_ABOUT
@@ -96,26 +197,92 @@ enum Command {
         long_about = EDIT_ABOUT
     )]
     Edit {
-        /// Position of the session to edit
-        #[arg(value_parser = parse_position)]
-        position: Position,
+        /// Positions of the sessions to edit
+        #[arg(value_parser = parse_positions, add = ArgValueCandidates::new(positions))]
+        positions: Vec<Position>,
         /// New session start
-        #[arg(short, value_parser = parse_abs)]
+        #[arg(short, value_parser = parse_abs, conflicts_with = "shift")]
         start: Option<Absolute>,
         /// New session end
-        #[arg(short, value_parser = parse_abs)]
+        #[arg(short, value_parser = parse_abs, conflicts_with = "shift")]
         end: Option<Absolute>,
         /// New notes
         #[arg(short, value_parser = parse_notes)]
         notes: Option<String>,
+        /// Offset to shift the sessions by
+        #[arg(long, value_parser = parse_offset, allow_hyphen_values = true)]
+        shift: Option<Duration>,
+        /// Only edit sessions whose notes contain this text
+        #[arg(short, value_parser = parse_notes)]
+        matching: Option<String>,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
     },
     #[clap(
-        about = "Remove a session",
+        about = "Remove sessions",
         long_about = REMOVE_ABOUT)]
     Remove {
-        /// Position of the session to remove
-        #[arg(value_parser = parse_position)]
+        /// Positions of the sessions to remove
+        #[arg(value_parser = parse_positions, add = ArgValueCandidates::new(positions))]
+        positions: Vec<Position>,
+        /// Only remove sessions whose notes contain this text
+        #[arg(short, value_parser = parse_notes)]
+        matching: Option<String>,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
+    },
+    #[clap(
+        about = "Split a session into two at a specific time",
+        long_about = SPLIT_SESSION_ABOUT)]
+    SplitSession {
+        /// Position of the session to split
+        #[arg(value_parser = parse_position, add = ArgValueCandidates::new(positions))]
         position: Position,
+        /// Time to split the session at
+        #[arg(value_parser = parse_abs)]
+        at: Absolute,
+        /// Notes for the first session
+        #[arg(long, value_parser = parse_notes)]
+        first: Option<String>,
+        /// Notes for the second session
+        #[arg(long, value_parser = parse_notes)]
+        second: Option<String>,
+    },
+    #[clap(
+        about = "Join two adjacent sessions into one",
+        long_about = JOIN_ABOUT)]
+    Join {
+        /// Position of the first session
+        #[arg(value_parser = parse_position, add = ArgValueCandidates::new(positions))]
+        first: Position,
+        /// Position of the second session
+        #[arg(value_parser = parse_position, add = ArgValueCandidates::new(positions))]
+        second: Position,
+        /// Notes for the joined session
+        #[arg(short, value_parser = parse_notes)]
+        notes: Option<String>,
+    },
+    #[clap(
+        about = "Move sessions of the active activity to another activity",
+        long_about = MOVE_ABOUT)]
+    Move {
+        /// Positions of the sessions to move
+        #[arg(required = true, value_parser = parse_positions, add = ArgValueCandidates::new(positions))]
+        positions: Vec<Position>,
+        /// Name of the activity to move the sessions to
+        #[arg(long, value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
+        to: String,
+    },
+    #[clap(
+        about = "Copy sessions of the active activity to another activity",
+        long_about = COPY_ABOUT)]
+    Copy {
+        /// Positions of the sessions to copy
+        #[arg(required = true, value_parser = parse_positions, add = ArgValueCandidates::new(positions))]
+        positions: Vec<Position>,
+        /// Name of the activity to copy the sessions to
+        #[arg(long, value_parser = parse_name, add = ArgValueCandidates::new(activity_names))]
+        to: String,
     },
     #[clap(
         about = "Display full session history, or sessions in a specific time range",
@@ -124,13 +291,138 @@ enum Command {
         #[command(subcommand)]
         range_command: Option<RangeCommand>,
     },
+    #[clap(
+        about = "Browse and edit sessions interactively",
+        long_about = TUI_ABOUT)]
+    Tui,
+    #[clap(
+        about = "Search the notes of sessions",
+        long_about = SEARCH_ABOUT)]
+    Search {
+        /// Text to search for
+        pattern: String,
+        /// Treat <PATTERN> as a regular expression
+        #[arg(short, long)]
+        regex: bool,
+        /// Ignore case when matching
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Search all activities
+        #[arg(long)]
+        all: bool,
+        /// Include archived activities
+        #[arg(long, requires = "all")]
+        include_archived: bool,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
+    },
+    #[clap(
+        about = "Export sessions to an iCalendar file",
+        long_about = EXPORT_ABOUT)]
+    Export {
+        /// File to write to, instead of printing the calendar
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Export all activities
+        #[arg(long)]
+        all: bool,
+        /// Include archived activities
+        #[arg(long, requires = "all")]
+        include_archived: bool,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
+    },
+    #[clap(
+        about = "Import the events of an iCalendar file as sessions of the active activity",
+        long_about = IMPORT_ABOUT)]
+    Import {
+        /// iCalendar file to import
+        file: PathBuf,
+    },
+    #[clap(
+        about = "Display untracked time during working hours",
+        long_about = GAPS_ABOUT)]
+    Gaps {
+        /// Minimum length in minutes of gaps to display
+        #[arg(short, long, default_value_t = 15)]
+        min: u32,
+        /// Assign each gap to an activity
+        #[arg(short, long)]
+        interactive: bool,
+        #[command(subcommand)]
+        range_command: Option<RangeCommand>,
+    },
+    #[clap(
+        about = "Watch for idle time, ending the ongoing session when idle",
+        long_about = DAEMON_ABOUT)]
+    Daemon {
+        /// Number of seconds between checks
+        #[arg(short, long, default_value_t = 30)]
+        interval: u64,
+    },
+    /// Display the idle intervals recorded by track daemon
+    Idle {
+        /// Clear the recorded idle intervals
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Display sessions of different activities that overlap
+    Check,
+    #[clap(
+        about = "Check the stored data for problems",
+        long_about = DOCTOR_ABOUT)]
+    Doctor {
+        /// Fix the problems found
+        #[arg(long)]
+        fix: bool,
+    },
     #[clap(
         about = "Display full session statistics, or session statistics in a specific time range",
         long_about = STATS_ABOUT)]
     Stats {
+        /// Display statistics across all activities
+        #[arg(long)]
+        all: bool,
+        /// Include archived activities
+        #[arg(long, requires = "all")]
+        include_archived: bool,
         #[command(subcommand)]
         range_command: Option<RangeCommand>,
     },
+    #[clap(
+        about = "Output a script that enables shell completion",
+        long_about = COMPLETIONS_ABOUT)]
+    Completions {
+        /// Shell to enable completion in
+        #[arg(value_parser = ["bash", "zsh", "fish", "elvish", "powershell"])]
+        shell: String,
+    },
+    #[clap(
+        about = "Merge the stored data with another data directory, such as a shared folder",
+        long_about = SYNC_ABOUT)]
+    Sync {
+        /// Data directory to sync with
+        dir: PathBuf,
+    },
+    #[clap(
+        about = "Move the stored data to another storage backend",
+        long_about = MIGRATE_ABOUT)]
+    Migrate {
+        /// Storage backend to move the data to
+        #[arg(long, value_parser = ["files", "sqlite"])]
+        to: String,
+    },
+    #[clap(
+        about = "Serve a JSON API for other programs to use the data",
+        long_about = SERVE_ABOUT)]
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        bind: String,
+        /// Token that requests must give as "Authorization: Bearer <TOKEN>"
+        #[arg(long)]
+        token: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -175,6 +467,7 @@ enum RangeCommand {
 }
 
 fn main() {
+    CompleteEnv::with_factory(Cli::command).complete();
     if let Err(e) = run() {
         print!("{e}");
     }
@@ -183,41 +476,145 @@ fn main() {
 fn run() -> Result<()> {
     use Command::*;
     let cli = Cli::try_parse()?;
+    if let Status { template, json } = cli.command {
+        // track status must never block or fail, so it does not wait for the lock, and shows no
+        // status if the data cannot be opened
+        let storage = storage::open().unwrap_or_else(|_| Box::new(MemoryStorage::default()));
+        let ctx = Context::new(&*storage, &SystemClock, io::empty());
+        return commands::status(&ctx, template, json);
+    }
+    let storage = storage::open()?;
+    let ctx =
+        Context::new(&*storage, &SystemClock, io::stdin().lock()).locking(storage::data_dir()?);
 
     match cli.command {
-        New { name } => commands::create(name),
-        Set { name } => commands::set(name),
-        Delete { name } => commands::delete(name),
-        Rename { from, to } => commands::rename(from, to),
-        Active => commands::current(),
-        All => commands::all(),
-        Start => commands::start(),
-        End { notes } => commands::end(notes),
-        Cancel => commands::cancel(),
-        Ongoing => commands::ongoing(),
-        Add { start, end, notes } => commands::add(start, end, notes),
+        New { name } => commands::create(&ctx, name),
+        Set { name } => commands::set(&ctx, name),
+        Delete { name } => commands::delete(&ctx, name),
+        Rename { from, to } => commands::rename(&ctx, from, to),
+        Archive { name } => commands::archive(&ctx, name),
+        Unarchive { name } => commands::unarchive(&ctx, name),
+        Merge { from, into } => commands::merge(&ctx, from, into),
+        Split {
+            name,
+            matching,
+            range_command,
+        } => commands::split(&ctx, name, get_filter(Vec::new(), range_command, matching)),
+        Config { key, value, reset } => commands::config(&ctx, key, value, reset),
+        Active => commands::current(&ctx),
+        All { archived } => commands::all(&ctx, archived),
+        Start => commands::start(&ctx),
+        End { notes } => commands::end(&ctx, notes),
+        Cancel => commands::cancel(&ctx),
+        Switch { name, notes } => commands::switch(&ctx, name, notes),
+        Timer { length, notes } => commands::timer(&ctx, length, notes),
+        Pomodoro { rounds, notes } => commands::pomodoro(&ctx, rounds, notes),
+        Ongoing { watch } => {
+            if watch {
+                commands::watch(&ctx)
+            } else {
+                commands::ongoing(&ctx)
+            }
+        }
+        Status { .. } => unreachable!("track status is run before the data is opened"),
+        Add { start, end, notes } => commands::add(&ctx, start, end, notes),
         Past {
             weeks,
             days,
             hours,
             minutes,
             notes,
-        } => commands::past(weeks, days, hours, minutes, notes),
+        } => commands::past(&ctx, weeks, days, hours, minutes, notes),
         Edit {
-            position,
+            positions,
             start,
             end,
             notes,
-        } => commands::edit(position, start, end, notes),
-        Remove { position } => commands::remove(position),
+            shift,
+            matching,
+            range_command,
+        } => commands::edit(
+            &ctx,
+            get_filter(positions, range_command, matching),
+            start,
+            end,
+            notes,
+            shift,
+        ),
+        Remove {
+            positions,
+            matching,
+            range_command,
+        } => commands::remove(&ctx, get_filter(positions, range_command, matching)),
+        SplitSession {
+            position,
+            at,
+            first,
+            second,
+        } => commands::split_session(&ctx, position, at, first, second),
+        Join {
+            first,
+            second,
+            notes,
+        } => commands::join(&ctx, first, second, notes),
+        Move { positions, to } => commands::move_sessions(&ctx, positions, to),
+        Copy { positions, to } => commands::copy(&ctx, positions, to),
         View { range_command } => {
             let (start, end) = get_bounds(range_command);
-            commands::view(start, end)
+            commands::view(&ctx, start, end)
         }
-        Stats { range_command } => {
+        Tui => commands::tui(&ctx),
+        Search {
+            pattern,
+            regex,
+            ignore_case,
+            all,
+            include_archived,
+            range_command,
+        } => {
             let (start, end) = get_bounds(range_command);
-            commands::stats(start, end)
+            let all = all.then_some(include_archived);
+            commands::search(&ctx, pattern, regex, ignore_case, start, end, all)
         }
+        Export {
+            output,
+            all,
+            include_archived,
+            range_command,
+        } => {
+            let (start, end) = get_bounds(range_command);
+            let all = all.then_some(include_archived);
+            commands::export(&ctx, start, end, all, output)
+        }
+        Import { file } => commands::import(&ctx, file),
+        Gaps {
+            min,
+            interactive,
+            range_command,
+        } => {
+            let (start, end) = get_bounds(range_command);
+            commands::gaps(&ctx, start, end, min, interactive)
+        }
+        Daemon { interval } => commands::daemon(&ctx, interval),
+        Idle { clear } => commands::idle(&ctx, clear),
+        Check => commands::check(&ctx),
+        Doctor { fix } => commands::doctor(&ctx, fix),
+        Stats {
+            all,
+            include_archived,
+            range_command,
+        } => {
+            let (start, end) = get_bounds(range_command);
+            if all {
+                commands::stats_all(&ctx, start, end, include_archived)
+            } else {
+                commands::stats(&ctx, start, end)
+            }
+        }
+        Completions { shell } => commands::completions(&shell),
+        Sync { dir } => commands::sync(&ctx, dir),
+        Migrate { to } => commands::migrate(&to),
+        Serve { bind, token } => commands::serve(bind, token),
     }
 }
 
@@ -250,6 +647,54 @@ fn get_bounds(command: Option<RangeCommand>) -> (Bound, Bound) {
     }
 }
 
+fn get_filter(
+    positions: Vec<Position>,
+    command: Option<RangeCommand>,
+    notes: Option<String>,
+) -> Filter {
+    let (from, to) = get_bounds(command);
+    Filter {
+        positions,
+        from,
+        to,
+        notes,
+    }
+}
+
+fn activity_names() -> Vec<CompletionCandidate> {
+    names_candidates(None)
+}
+
+fn unarchived_names() -> Vec<CompletionCandidate> {
+    names_candidates(Some(false))
+}
+
+fn archived_names() -> Vec<CompletionCandidate> {
+    names_candidates(Some(true))
+}
+
+fn names_candidates(archived: Option<bool>) -> Vec<CompletionCandidate> {
+    let Ok(storage) = storage::open() else {
+        return Vec::new();
+    };
+    let ctx = Context::new(&*storage, &SystemClock, io::empty());
+    commands::activity_names(&ctx, archived)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+fn positions() -> Vec<CompletionCandidate> {
+    let Ok(storage) = storage::open() else {
+        return Vec::new();
+    };
+    let ctx = Context::new(&*storage, &SystemClock, io::empty());
+    commands::positions(&ctx)
+        .into_iter()
+        .map(|(position, session)| CompletionCandidate::new(position).help(Some(session.into())))
+        .collect()
+}
+
 fn parse_name(s: &str) -> Result<String, String> {
     let s = s.trim();
     if s.is_empty() {
@@ -265,12 +710,48 @@ fn parse_notes(s: &str) -> Result<String, String> {
 fn parse_position(s: &str) -> Result<Position, String> {
     if s == "last" {
         return Ok(Position::Last);
+    } else if let Some(id) = s.strip_prefix('@') {
+        if !id.is_empty() && id.len() <= 16 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Position::Id(id.to_ascii_lowercase()));
+        }
+        return Err("id must be in the form [@id], with up to 16 hexadecimal digits".to_string());
     } else if let Ok(i) = s.parse() {
         if i > 0 {
             return Ok(Position::Index(i));
         }
     }
-    Err("index must be either [last] or a positive integer".to_string())
+    Err("index must be either [last], [@id] or a positive integer".to_string())
+}
+
+fn parse_positions(s: &str) -> Result<Position, String> {
+    if let Some((first, last)) = s.split_once('-') {
+        if let (Ok(first), Ok(last)) = (first.parse(), last.parse()) {
+            if first > 0 && first <= last {
+                return Ok(Position::Range(first, last));
+            }
+        }
+        return Err("range must be in the form [first-last], with 0 < first <= last".to_string());
+    }
+    parse_position(s)
+}
+
+fn parse_length(s: &str) -> Result<Duration, String> {
+    match track::parse_duration(s) {
+        Some(duration) if !duration.is_zero() => Ok(duration),
+        _ => Err("length must be in the form [Nw][Nd][Nh][Nm], e.g. 1h30m".to_string()),
+    }
+}
+
+fn parse_offset(s: &str) -> Result<Duration, String> {
+    let (negative, rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    match track::parse_duration(rest) {
+        Some(duration) if duration.is_zero() => Err("offset must not be zero".to_string()),
+        Some(duration) => Ok(if negative { -duration } else { duration }),
+        None => Err("offset must be in the form [+|-][Nw][Nd][Nh][Nm], e.g. -1h30m".to_string()),
+    }
 }
 
 fn parse_date(s: &str) -> Result<NaiveDate, String> {
@@ -279,14 +760,8 @@ fn parse_date(s: &str) -> Result<NaiveDate, String> {
 }
 
 fn parse_abs(s: &str) -> Result<Absolute, String> {
-    if let Ok(date_time) = NaiveDateTime::parse_from_str(s, "%d/%m/%y-%R") {
-        return Ok(Absolute::DateTime(date_time));
-    } else if let Ok(date) = NaiveDate::parse_from_str(s, "%d/%m/%y") {
-        return Ok(Absolute::Date(date));
-    } else if let Ok(time) = NaiveTime::parse_from_str(s, "%R") {
-        return Ok(Absolute::Time(time));
-    }
-    Err("must be in the form [dd/mm/yy] or [HH:MM] or [dd/mm/yy-HH:MM]".to_string())
+    Absolute::parse(s)
+        .ok_or_else(|| "must be in the form [dd/mm/yy] or [HH:MM] or [dd/mm/yy-HH:MM]".to_string())
 }
 
 fn to_bound(abs: Option<Absolute>) -> Bound {
@@ -297,6 +772,87 @@ fn to_bound(abs: Option<Absolute>) -> Bound {
     }
 }
 
+const TIMER_ABOUT: &str = "Track a session for a specific length of time
+
+Starts a session of the active activity, displays the time remaining, and ends the session once
+<LENGTH> has passed
+If interrupted, the session is left ongoing, and can be ended with track end";
+
+const POMODORO_ABOUT: &str = "Track sessions with breaks using the Pomodoro technique
+
+Tracks \"pomodoro-rounds\" sessions of the active activity, each \"pomodoro-work\" long, with a
+break of \"pomodoro-break\" after each session and \"pomodoro-long-break\" after the last
+These are set with track config
+Each session's notes are marked with the round, e.g. [pomodoro 1/4]
+If interrupted, the session is left ongoing, and can be ended with track end";
+
+const STATUS_ABOUT: &str = "Display the ongoing session briefly, for shell prompts and status bars
+
+Nothing is displayed if there is no ongoing session of the active activity, and errors are never displayed
+
+<TEMPLATE> can contain the following, which are replaced by their values:
+{activity} Name of the active activity
+{elapsed}  Duration of the ongoing session, as H:MM:SS
+{start}    Start time of the ongoing session, as HH:MM
+{today}    Time spent on the active activity today, as H:MM:SS
+{notes}    Notes of the ongoing session";
+
+const COMPLETIONS_ABOUT: &str = "Output a script that enables shell completion
+
+Activity names and session positions are completed using the current data
+
+bash:       add   source <(track completions bash)          to ~/.bashrc
+zsh:        add   source <(track completions zsh)           to ~/.zshrc
+fish:       add   track completions fish | source           to ~/.config/fish/config.fish
+elvish:     add   eval (track completions elvish | slurp)   to ~/.config/elvish/rc.elv
+powershell: add   track completions powershell | Out-String | Invoke-Expression   to $PROFILE";
+
+const SYNC_ABOUT: &str =
+    "Merge the stored data with another data directory, such as a shared folder
+
+<DIR> must be an existing directory, which is filled by the first sync
+Keep <DIR> in a folder synced by Syncthing or Dropbox, or in a git repository, and sync each machine
+with it to share sessions between machines
+
+Changes made on each side since the last sync are combined: activities are matched by name,
+or by their sessions if they were renamed, and sessions by id. Sessions that were changed on both
+sides, or changed on one and removed on the other, are reported and kept, as are sessions that
+overlap after merging
+Both directories are locked while they are merged
+Settings and idle intervals are not synced";
+
+const MIGRATE_ABOUT: &str = "Move the stored data to another storage backend
+
+files:  one file per activity, which is the default
+sqlite: a single SQLite database, where sessions are indexed by time so that track view and
+        track stats only read the sessions in the time range
+
+SQLite is only available if track was built with the sqlite feature
+Once migrated, the data is only read from and written to the new backend";
+
+const SERVE_ABOUT: &str = "Serve a JSON API for other programs to use the data
+
+GET    /status                  status of the active activity, as in track status --json
+GET    /activities              all activities
+POST   /activities              create an activity, with body {\"name\": ...}
+GET    /activities/<NAME>       one activity
+PATCH  /activities/<NAME>       rename or archive an activity, with body {\"name\": ..., \"archived\": ...}
+DELETE /activities/<NAME>       delete an activity
+PUT    /active                  set the active activity, with body {\"name\": ...}
+POST   /start                   start a session of the active activity
+POST   /end                     end the ongoing session, with optional body {\"notes\": ...}
+POST   /cancel                  cancel the ongoing session
+POST   /switch                  end any ongoing session and start one of another activity,
+                                with body {\"name\": ..., \"notes\": ...}
+GET    /sessions                sessions of the active activity
+GET    /stats                   session statistics of the active activity
+
+/sessions and /stats take the optional query parameters activity=<NAME>, and from and to as
+RFC 3339 times, e.g. from=2024-06-10T09:00:00Z
+Errors are returned as {\"error\": ...}
+
+Requests that change the data wait a few seconds for other track commands changing it to finish";
+
 const ADD_ABOUT: &str = "Add a new session
     
 <START>: [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
@@ -307,38 +863,160 @@ const ADD_ABOUT: &str = "Add a new session
          [dd/mm/yy]       - 00:00 the day after dd/mm/yy
          [HH:MM]          - HH:MM on <START>'s date";
 
-const EDIT_ABOUT: &str = "Edit a session
+const EDIT_ABOUT: &str = "Edit sessions
+
+Sessions are selected by <POSITIONS>, <MATCHING> and [COMMAND], and must match all given
+
+<POSITIONS>: [index]          - index of the session, as shown in track view
+             [first-last]     - indices from first to last inclusive
+             \"last\"           - last recorded session
+             [@id]            - session whose id begins with id, as shown in track view
+    
+<START>:     [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
+             [dd/mm/yy]       - 00:00 on dd/mm/yy
+             [HH:MM]          - HH:MM on today's date
+             omitted          - leave unchanged
+
+<END>:       [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
+             [dd/mm/yy]       - 00:00 the day after dd/mm/yy
+             [HH:MM]          - HH:MM on <START>'s date
+             omitted          - leave unchanged
+
+<NOTES>:     [string]         - string
+             whitespace       - remove notes
+             omitted          - leave unchanged
+
+<SHIFT>:     [+|-][Nw][Nd][Nh][Nm] - move start and end by this offset
+             omitted          - leave unchanged
+
+<START> and <END> can only be given when a single session is selected";
+
+const REMOVE_ABOUT: &str = "Remove sessions
+
+Sessions are selected by <POSITIONS>, <MATCHING> and [COMMAND], and must match all given
+
+<POSITIONS>: [index]          - index of the session, as shown in track view
+             [first-last]     - indices from first to last inclusive
+             \"last\"           - last recorded session
+             [@id]            - session whose id begins with id, as shown in track view";
+
+const SPLIT_ABOUT: &str = "Move matching sessions of the active activity into a new activity
+
+Sessions are selected by <MATCHING> and [COMMAND], and must match all given";
+
+const SPLIT_SESSION_ABOUT: &str = "Split a session into two at a specific time
 
 <POSITION>: [index]          - index of the session, as shown in track view
             \"last\"           - last recorded session
-    
-<START>:    [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
+            [@id]            - session whose id begins with id, as shown in track view
+
+<AT>:       [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
             [dd/mm/yy]       - 00:00 on dd/mm/yy
-            [HH:MM]          - HH:MM on today's date
-            omitted          - leave unchanged
+            [HH:MM]          - HH:MM on the session's start date
 
-<END>:      [dd/mm/yy-HH:MM] - HH:MM on dd/mm/yy
-            [dd/mm/yy]       - 00:00 the day after dd/mm/yy
-            [HH:MM]          - HH:MM on <START>'s date
-            omitted          - leave unchanged
+<FIRST>:    [string]         - string
+            whitespace       - remove notes
+            omitted          - keep the session's notes
 
-<NOTES>:    [string]         - string
+<SECOND>:   [string]         - string
             whitespace       - remove notes
-            omitted          - leave unchanged";
+            omitted          - keep the session's notes";
 
-const REMOVE_ABOUT: &str = "Remove a session
+const JOIN_ABOUT: &str = "Join two adjacent sessions into one
 
-<POSITION>: [index]          - index of the session, as shown in track view
-            \"last\"           - last recorded session";
+The joined session starts at the start of the first session and ends at the end of the second
+If the sessions are more than an hour apart, you are asked before the gap is included
+
+<FIRST>, <SECOND>: [index]   - index of the session, as shown in track view
+                   \"last\"    - last recorded session
+                   [@id]     - session whose id begins with id, as shown in track view
+
+<NOTES>:           [string]  - string
+                   whitespace - remove notes
+                   omitted   - combine the notes of both sessions";
+
+const MOVE_ABOUT: &str = "Move sessions of the active activity to another activity
+
+<POSITIONS>: [index]          - index of the session, as shown in track view
+             [first-last]     - indices from first to last inclusive
+             \"last\"           - last recorded session
+             [@id]            - session whose id begins with id, as shown in track view
+
+No sessions are moved if any would overlap a session of <TO>";
+
+const COPY_ABOUT: &str = "Copy sessions of the active activity to another activity
+
+<POSITIONS>: [index]          - index of the session, as shown in track view
+             [first-last]     - indices from first to last inclusive
+             \"last\"           - last recorded session
+             [@id]            - session whose id begins with id, as shown in track view
+
+No sessions are copied if any would overlap a session of <TO>";
 
 const VIEW_ABOUT: &str = "Display full session history, or sessions in a specific time range
 
 Omit [COMMAND] for full session history";
 
+const TUI_ABOUT: &str = "Browse and edit sessions interactively
+
+[tab]     Switch between the activities and sessions panes
+[up/down] Select an activity or session
+[r]       Change the time range of sessions and statistics shown
+[s/e/n]   Edit the start, end or notes of the selected session
+[d]       Remove the selected session
+[u]       Undo the last removal
+[q]       Quit";
+
+const SEARCH_ABOUT: &str = "Search the notes of sessions
+
+By default <PATTERN> matches any notes containing it, and only the active activity is searched
+Use [COMMAND] to only search sessions in a specific time range";
+
+const EXPORT_ABOUT: &str = "Export sessions to an iCalendar file
+
+Each session becomes an event with the activity as its title and the notes as its description
+Events are identified by the ids of their sessions, so importing an export again into a calendar
+updates the events instead of duplicating them
+Omit [COMMAND] to export all sessions";
+
+const IMPORT_ABOUT: &str =
+    "Import the events of an iCalendar file as sessions of the active activity
+
+Each event becomes a session with the description of the event as its notes, or its title when it
+has no description
+Events that last all day, end in the future or overlap an existing session are skipped and
+reported, and times in a named time zone are read as local times
+Only the first occurrence of a repeating event is imported";
+
+const GAPS_ABOUT: &str = "Display untracked time during working hours
+
+Time is untracked if it is not covered by a session of any activity
+Working hours are set with track config, using \"work-start\", \"work-end\" and \"work-days\"
+Use --interactive to assign each gap to an activity as a new session";
+
+const DAEMON_ABOUT: &str = "Watch for idle time, ending the ongoing session when idle
+
+Idle time is read from \"idle-command\" or \"idle-file\", set with track config
+When idle for longer than \"idle-threshold\", the ongoing session of the active activity is ended
+at the time the user became idle, and the idle interval is recorded for track idle
+If \"idle-action\" is \"pause\", a new session is started when the user is active again";
+
+const DOCTOR_ABOUT: &str = "Check the stored data for problems
+
+Checks that every activity has a readable file, that sessions are in order, do not overlap and
+end after they start, and that ongoing sessions are valid
+
+Use --fix to fix the problems found. Overlapping sessions are joined, ongoing sessions that are
+not valid are cancelled, and activity files that are not part of any activity are recovered as
+new activities if they contain sessions, or deleted otherwise";
+
 const STATS_ABOUT: &str =
     "Display full session statistics, or sessions statistics in a specific time range
 
-Omit [COMMAND] for full session statistics";
+Omit [COMMAND] for full session statistics
+
+Use --all for statistics across all activities, and --include-archived to
+include archived activities";
 
 const PAST_ABOUT: &str = "Sessions ranging between a specific amount of time in the past, and now
 
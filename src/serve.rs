@@ -0,0 +1,402 @@
+use std::{collections::BTreeMap, fmt, io, net::SocketAddr, path::PathBuf, time};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use serde_json::{json, Value};
+use tiny_http::{Header, Request, Response};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    commands::{self, Context},
+    data::{Activity, ActivityInfo, Data},
+    stats::{read_in_range, summary_in_range},
+    storage::{self, Storage},
+    DateTime,
+};
+
+/// How long a request waits for a track command that is using the data to finish
+const LOCK_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+/// The JSON API of track serve, answering one request at a time
+pub struct Server {
+    http: tiny_http::Server,
+    dir: PathBuf,
+    token: Option<String>,
+}
+
+impl Server {
+    /// Listens on `addr` for requests about the data in `dir`, which must carry `token` as a
+    /// bearer token if one is given
+    pub fn bind(addr: &str, dir: PathBuf, token: Option<String>) -> Result<Self> {
+        let http = tiny_http::Server::http(addr)
+            .map_err(|e| anyhow!("error: Failed to listen on {addr}: {e}"))?;
+        Ok(Self { http, dir, token })
+    }
+
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    pub fn run(&self) -> Result<()> {
+        loop {
+            let mut request = self.http.recv()?;
+            let (status, body) = self.answer(&mut request);
+            let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+            let response = Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(header);
+            // The client may have gone away, which should not stop the server
+            let _ = request.respond(response);
+        }
+    }
+
+    fn answer(&self, request: &mut Request) -> (u16, Value) {
+        if let Some(token) = &self.token {
+            let bearer = format!("Bearer {token}");
+            let authorized = request.headers().iter().any(|header| {
+                header.field.equiv("Authorization")
+                    && same(header.value.as_bytes(), bearer.as_bytes())
+            });
+            if !authorized {
+                return failure(401, "A valid token is required");
+            }
+        }
+        let mut body = String::new();
+        if request.as_reader().read_to_string(&mut body).is_err() {
+            return failure(400, "The request body must be UTF-8");
+        }
+        let storage = match storage::open_dir(&self.dir) {
+            Ok(storage) => storage,
+            Err(e) => return failure(500, &e.to_string()),
+        };
+        let ctx = Context::new(&*storage, &SystemClock, io::empty()).locking(self.dir.clone());
+        let method = request.method().as_str().to_string();
+        // Requests that change the data wait only so long for track commands changing it
+        let _lock = if method == "GET" {
+            None
+        } else {
+            match ctx.try_lock(LOCK_TIMEOUT) {
+                Ok(Some(lock)) => Some(lock),
+                Ok(None) => return failure(503, "The data is in use by another track command"),
+                Err(e) => return failure(500, &e.to_string()),
+            }
+        };
+        respond(&ctx, &method, request.url(), &body)
+    }
+}
+
+/// Answers a request to the API with a status code and a JSON body
+pub fn handle(
+    storage: &dyn Storage,
+    clock: &dyn Clock,
+    method: &str,
+    url: &str,
+    body: &str,
+) -> (u16, Value) {
+    respond(
+        &Context::new(storage, clock, io::empty()),
+        method,
+        url,
+        body,
+    )
+}
+
+fn respond(ctx: &Context, method: &str, url: &str, body: &str) -> (u16, Value) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let path: Option<Vec<_>> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(decode)
+        .collect();
+    let query: Option<BTreeMap<_, _>> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((decode_query(key)?, decode_query(value)?))
+        })
+        .collect();
+    let (Some(path), Some(query)) = (path, query) else {
+        return failure(400, "The URL is not correctly encoded");
+    };
+    let body = if body.trim().is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_str(body) {
+            Ok(body) => body,
+            Err(_) => return failure(400, "The request body must be JSON"),
+        }
+    };
+    let path: Vec<_> = path.iter().map(String::as_str).collect();
+    match route(ctx, method, &path, &query, &body) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => failure(404, "No such endpoint"),
+        Err(e) => failure(error_status(&e), &e.to_string()),
+    }
+}
+
+/// An activity named by a request that does not exist
+#[derive(Debug)]
+struct NotFound(String);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// The activity named `name`, failing with `NotFound` if there is none
+fn find<'a>(data: &'a Data, name: &str) -> Result<&'a ActivityInfo> {
+    commands::find(data, name).map_err(|e| NotFound(e.to_string()).into())
+}
+
+/// The status code for a request that failed with `e`. Messages written for the user start with
+/// "error: " and describe a problem with the request, while any other error, or one caused by
+/// another error, comes from reading or writing the data
+fn error_status(e: &anyhow::Error) -> u16 {
+    if e.is::<NotFound>() {
+        404
+    } else if e.to_string().starts_with("error: ") && e.source().is_none() {
+        400
+    } else {
+        500
+    }
+}
+
+fn route(
+    ctx: &Context,
+    method: &str,
+    path: &[&str],
+    query: &BTreeMap<String, String>,
+    body: &Value,
+) -> Result<Option<(u16, Value)>> {
+    let storage = ctx.storage;
+    let status = |ctx: &Context| (200, commands::status_json(ctx));
+    Ok(Some(match (method, path) {
+        ("GET", ["status"]) => status(ctx),
+        ("GET", ["activities"]) => {
+            let data = Data::read(storage)?;
+            let activities = data
+                .activities
+                .iter()
+                .map(|info| activity(storage, &data, info))
+                .collect::<Result<_>>()?;
+            (200, Value::Array(activities))
+        }
+        ("POST", ["activities"]) => {
+            let name = name(body)?;
+            commands::create_activity(ctx, &name)?;
+            let data = Data::read(storage)?;
+            (201, activity(storage, &data, find(&data, &name)?)?)
+        }
+        ("GET", ["activities", name]) => {
+            let data = Data::read(storage)?;
+            (200, activity(storage, &data, find(&data, name)?)?)
+        }
+        ("PATCH", ["activities", name]) => {
+            find(&Data::read(storage)?, name)?;
+            let mut name = name.to_string();
+            if body.get("name").is_some() {
+                let to = self::name(body)?;
+                commands::rename_activity(ctx, &name, &to)?;
+                name = to;
+            }
+            if let Some(archived) = body.get("archived") {
+                let Some(archived) = archived.as_bool() else {
+                    bail!("error: \"archived\" must be true or false");
+                };
+                let data = Data::read(storage)?;
+                if archived != data.is_archived(find(&data, &name)?.id) {
+                    if archived {
+                        commands::archive_activity(ctx, &name)?;
+                    } else {
+                        commands::unarchive_activity(ctx, &name)?;
+                    }
+                }
+            }
+            let data = Data::read(storage)?;
+            (200, activity(storage, &data, find(&data, &name)?)?)
+        }
+        ("DELETE", ["activities", name]) => {
+            find(&Data::read(storage)?, name)?;
+            // The request is the confirmation that track delete asks for
+            commands::delete_activity(ctx, name)?;
+            (200, json!({ "deleted": name }))
+        }
+        ("PUT", ["active"]) => {
+            let name = name(body)?;
+            find(&Data::read(storage)?, &name)?;
+            commands::set_active(ctx, &name)?;
+            status(ctx)
+        }
+        ("POST", ["start"]) => {
+            commands::start_session(ctx)?;
+            status(ctx)
+        }
+        ("POST", ["end"]) => {
+            commands::end_session(ctx, notes(body)?, None)?;
+            status(ctx)
+        }
+        ("POST", ["cancel"]) => {
+            commands::cancel_session(ctx)?;
+            status(ctx)
+        }
+        ("POST", ["switch"]) => {
+            let name = name(body)?;
+            find(&Data::read(storage)?, &name)?;
+            commands::switch_to(ctx, &name, notes(body)?, None)?;
+            status(ctx)
+        }
+        ("GET", ["sessions"]) => (200, sessions(ctx, query)?),
+        ("GET", ["stats"]) => (200, stats(ctx, query)?),
+        _ => return Ok(None),
+    }))
+}
+
+fn sessions(ctx: &Context, query: &BTreeMap<String, String>) -> Result<Value> {
+    let data = Data::read(ctx.storage)?;
+    let info = selected(&data, query)?;
+    let mut sessions = Vec::new();
+    if let Some((from, to)) = range(ctx.storage, info.id, query)? {
+        let (offset, activity) = read_in_range(ctx.storage, info.id, from, to)?;
+        for (k, session) in activity.sessions.iter().enumerate() {
+            sessions.push(json!({
+                "position": offset + k + 1,
+                "id": session.full_id(),
+                "start": session.start.to_rfc3339(),
+                "end": session.end.to_rfc3339(),
+                "notes": session.notes,
+            }));
+        }
+    }
+    Ok(json!({ "activity": info.name, "sessions": sessions }))
+}
+
+fn stats(ctx: &Context, query: &BTreeMap<String, String>) -> Result<Value> {
+    let data = Data::read(ctx.storage)?;
+    let info = selected(&data, query)?;
+    let range = range(ctx.storage, info.id, query)?;
+    let summary = match range {
+        Some((from, to)) => summary_in_range(ctx.storage, info.id, from, to)?,
+        None => None,
+    };
+    let seconds = |duration: chrono::Duration| duration.num_seconds();
+    Ok(json!({
+        "activity": info.name,
+        "from": range.map(|(from, _)| from.to_rfc3339()),
+        "to": range.map(|(_, to)| to.to_rfc3339()),
+        "sessions": summary.as_ref().map_or(0, |summary| summary.sessions),
+        "total": summary.as_ref().map_or(0, |summary| seconds(summary.total)),
+        "per_day": summary.as_ref().map_or(0, |summary| seconds(summary.per_day)),
+        "average": summary.as_ref().map_or(0, |summary| seconds(summary.average)),
+        "proportion": summary.as_ref().map_or(0., |summary| summary.proportion),
+    }))
+}
+
+fn activity(storage: &dyn Storage, data: &Data, info: &ActivityInfo) -> Result<Value> {
+    let ongoing = Activity::read(storage, info.id)?.ongoing;
+    Ok(json!({
+        "name": info.name,
+        "active": data.active.as_ref().is_some_and(|active| active.id == info.id),
+        "archived": data.is_archived(info.id),
+        "ongoing": ongoing.map(|start| start.to_rfc3339()),
+    }))
+}
+
+/// The activity named by the query, or the active activity
+fn selected<'a>(data: &'a Data, query: &BTreeMap<String, String>) -> Result<&'a ActivityInfo> {
+    match query.get("activity") {
+        Some(name) => find(data, name),
+        None => data
+            .active
+            .as_ref()
+            .ok_or_else(|| anyhow!("error: No activity currently active")),
+    }
+}
+
+/// The time range given by the query, where omitted bounds are the first and last recorded
+/// sessions, or `None` if a bound is omitted and there are no recorded sessions
+fn range(
+    storage: &dyn Storage,
+    id: u32,
+    query: &BTreeMap<String, String>,
+) -> Result<Option<(DateTime, DateTime)>> {
+    let recorded = storage.recorded_range(id)?;
+    let from = match query.get("from") {
+        Some(from) => Some(parse_time("from", from)?),
+        None => recorded.map(|(first, _)| first),
+    };
+    let to = match query.get("to") {
+        Some(to) => Some(parse_time("to", to)?),
+        None => recorded.map(|(_, last)| last),
+    };
+    let (Some(from), Some(to)) = (from, to) else {
+        return Ok(None);
+    };
+    if from >= to {
+        bail!("error: Start of range must be before end");
+    }
+    Ok(Some((from, to)))
+}
+
+fn parse_time(key: &str, s: &str) -> Result<DateTime> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| {
+            anyhow!("error: \"{key}\" must be an RFC 3339 time, e.g. 2024-06-10T09:00:00Z")
+        })
+}
+
+fn name(body: &Value) -> Result<String> {
+    match body.get("name").and_then(Value::as_str).map(str::trim) {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => bail!("error: \"name\" must be a non-empty string"),
+    }
+}
+
+fn notes(body: &Value) -> Result<String> {
+    match body.get("notes") {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(notes)) => Ok(notes.trim().to_string()),
+        Some(_) => bail!("error: \"notes\" must be a string"),
+    }
+}
+
+/// Decodes a percent-encoded part of a URL
+fn decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Decodes a key or value of a URL query, in which "+" stands for a space
+fn decode_query(s: &str) -> Option<String> {
+    decode(&s.replace('+', " "))
+}
+
+/// Compares `a` and `b` without stopping at the first difference, so that the time taken does not
+/// tell how much of a token was guessed correctly
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// A reply with an error message, without the "error: " that track prints before it
+fn failure(status: u16, message: &str) -> (u16, Value) {
+    let message = message.trim();
+    let message = message.strip_prefix("error: ").unwrap_or(message);
+    (status, json!({ "error": message }))
+}
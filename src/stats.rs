@@ -0,0 +1,217 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::Duration;
+
+use crate::{
+    data::{read_failed, Activity, ActivityInfo, Data},
+    parse_end, parse_start,
+    storage::Storage,
+    Bound, DateTime,
+};
+
+/// Statistics of the sessions of an activity in a time range
+pub struct Summary {
+    pub sessions: usize,
+    pub total: Duration,
+    pub per_day: Duration,
+    pub average: Duration,
+    pub proportion: f64,
+}
+
+impl Summary {
+    /// Statistics of `sessions` sessions that total `total` within the time range, or `None` if
+    /// there are none
+    pub fn new(sessions: usize, total: Duration, from: DateTime, to: DateTime) -> Option<Self> {
+        if sessions == 0 {
+            return None;
+        }
+        let proportion = total.num_seconds() as f64 / (to - from).num_seconds() as f64;
+        Some(Self {
+            sessions,
+            total,
+            per_day: Duration::seconds((proportion * 60. * 60. * 24.) as i64),
+            average: total / sessions as i32,
+            proportion,
+        })
+    }
+}
+
+impl Data {
+    /// Resolves `from` and `to` to a time range for the active activity, reading only its first
+    /// and last sessions
+    pub fn current_range(
+        &self,
+        storage: &dyn Storage,
+        from: Bound,
+        to: Bound,
+        now: DateTime,
+    ) -> Result<(DateTime, DateTime, &ActivityInfo)> {
+        let Some(info) = &self.active else {
+            bail!("error: No activity currently active");
+        };
+        let Some((first, last)) =
+            recorded_range(storage, &[info.id]).map_err(|e| read_failed(&info.name, e))?
+        else {
+            bail!("There are no recorded sessions of the active activity");
+        };
+        let (from, to) = convert_bounds(first, last, from, to, now)?;
+        Ok((from, to, info))
+    }
+}
+
+/// Start of the first and end of the last session of any of activities `ids`, or `None` if none
+/// of them have sessions
+pub fn recorded_range(storage: &dyn Storage, ids: &[u32]) -> Result<Option<(DateTime, DateTime)>> {
+    let mut recorded: Option<(DateTime, DateTime)> = None;
+    for &id in ids {
+        if let Some((first, last)) = storage.recorded_range(id)? {
+            recorded = Some(match recorded {
+                Some((min, max)) => (first.min(min), last.max(max)),
+                None => (first, last),
+            });
+        }
+    }
+    Ok(recorded)
+}
+
+/// Reads the sessions of activity `id` that overlap the time range, returning the index of the
+/// first of them among all its sessions, and an activity holding only them
+pub fn read_in_range(
+    storage: &dyn Storage,
+    id: u32,
+    from: DateTime,
+    to: DateTime,
+) -> Result<(usize, Activity)> {
+    let (offset, sessions) = storage.sessions_in_range(id, from, to)?;
+    Ok((
+        offset,
+        Activity {
+            sessions,
+            ..Activity::new()
+        },
+    ))
+}
+
+/// Statistics of the sessions of activity `id` in the time range, from the totals kept by the
+/// storage
+pub fn summary_in_range(
+    storage: &dyn Storage,
+    id: u32,
+    from: DateTime,
+    to: DateTime,
+) -> Result<Option<Summary>> {
+    let (sessions, total) = storage.time_in_range(id, from, to)?;
+    Ok(Summary::new(sessions, total, from, to))
+}
+
+impl Activity {
+    /// Resolves `from` and `to` to a time range, where omitted bounds are the first and last
+    /// recorded sessions
+    pub fn convert_bounds(
+        &self,
+        from: Bound,
+        to: Bound,
+        now: DateTime,
+    ) -> Result<(DateTime, DateTime)> {
+        let (Some(first), Some(last)) = (self.sessions.first(), self.sessions.last()) else {
+            bail!("There are no recorded sessions of the active activity");
+        };
+        convert_bounds(first.start, last.end, from, to, now)
+    }
+
+    /// Indices of the sessions that overlap the time range, as a half-open range
+    pub fn get_in_range(&self, from: DateTime, to: DateTime) -> (usize, usize) {
+        let i = self.sessions.partition_point(|session| session.end <= from);
+        let j = i + self.sessions[i..].partition_point(|session| session.start < to);
+        (i, j)
+    }
+
+    /// Statistics of the sessions in the time range, or `None` if there are none
+    pub fn summary(&self, from: DateTime, to: DateTime) -> Option<Summary> {
+        let (i, j) = self.get_in_range(from, to);
+        Summary::new(j - i, self.time_in_range(from, to), from, to)
+    }
+
+    /// Time spent in sessions within the time range, excluding any ongoing session
+    pub fn time_in_range(&self, from: DateTime, to: DateTime) -> Duration {
+        let (i, j) = self.get_in_range(from, to);
+        let mut time = Duration::zero();
+        for (k, session) in self.sessions.iter().enumerate().take(j).skip(i) {
+            let (mut start, mut end) = (session.start, session.end);
+            if k == i {
+                start = start.max(from);
+            }
+            if k == j - 1 {
+                end = end.min(to);
+            }
+            time += end - start;
+        }
+        time
+    }
+}
+
+/// Resolves `from` and `to` to a time range across several activities, where omitted bounds are
+/// the first and last recorded sessions of any of them
+pub fn convert_bounds_all<'a>(
+    recorded: impl Iterator<Item = &'a Activity> + Clone,
+    from: Bound,
+    to: Bound,
+    now: DateTime,
+) -> Result<(DateTime, DateTime)> {
+    let first = recorded
+        .clone()
+        .filter_map(|activity| activity.sessions.first())
+        .map(|session| session.start)
+        .min();
+    let last = recorded
+        .filter_map(|activity| activity.sessions.last())
+        .map(|session| session.end)
+        .max();
+    let (Some(first), Some(last)) = (first, last) else {
+        bail!("There are no recorded sessions");
+    };
+    convert_bounds(first, last, from, to, now)
+}
+
+/// Resolves `from` and `to` to a time range, where omitted bounds are `first` and `last`, and
+/// relative bounds are relative to `now`
+pub fn convert_bounds(
+    first: DateTime,
+    last: DateTime,
+    from: Bound,
+    to: Bound,
+    now: DateTime,
+) -> Result<(DateTime, DateTime)> {
+    let from = match from {
+        Bound::Absolute(abs) => parse_start(abs, now),
+        Bound::Ago {
+            weeks,
+            days,
+            hours,
+            minutes,
+        } => {
+            if weeks == 0 && days == 0 && hours == 0 && minutes == 0 {
+                first
+            } else {
+                now.checked_sub_signed(Duration::minutes(
+                    minutes as i64
+                        + hours as i64 * 60
+                        + days as i64 * 24 * 60
+                        + weeks as i64 * 7 * 24 * 60,
+                ))
+                .ok_or_else(|| anyhow!("error: Start of range is too far in the past"))?
+            }
+        }
+        Bound::None => first,
+        Bound::Now => bail!("error: Start of range cannot be now"),
+    };
+    let to = match to {
+        Bound::Absolute(abs) => parse_end(abs, from),
+        Bound::None => last,
+        Bound::Now => now,
+        Bound::Ago { .. } => bail!("error: End of range must be a specific time"),
+    };
+    if from >= to {
+        bail!("error: Start of range must be before end");
+    }
+    Ok((from, to))
+}
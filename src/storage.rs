@@ -0,0 +1,277 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::{self, File, TryLockError},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    thread,
+    time::{self, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Duration;
+
+use crate::{
+    data::{Activity, Session},
+    DateTime,
+};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+/// Names of the stored files other than activities
+pub const FILES: [&str; 4] = ["data", "config", "idle", "sync"];
+
+/// Name of the SQLite database in the data directory, which is used instead of the files if it
+/// exists
+pub const DATABASE: &str = "track.db";
+
+/// Where the data is stored. Activities are stored as files named by their id, unless the
+/// storage overrides the activity methods
+pub trait Storage {
+    /// Contents of the file `name`, or `None` if it does not exist
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>>;
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()>;
+    fn remove(&self, name: &str) -> Result<()>;
+    /// Names of all stored files
+    fn names(&self) -> Result<Vec<String>>;
+
+    /// Activity `id`, or `None` if it is not stored
+    fn read_activity(&self, id: u32) -> Result<Option<Activity>> {
+        self.read(&id.to_string())?
+            .map(|encoded| Activity::decode(&encoded))
+            .transpose()
+    }
+
+    fn write_activity(&self, id: u32, activity: &Activity) -> Result<()> {
+        self.write(&id.to_string(), &activity.encode()?)
+    }
+
+    fn remove_activity(&self, id: u32) -> Result<()> {
+        self.remove(&id.to_string())
+    }
+
+    /// Ids of all stored activities, in order
+    fn activity_ids(&self) -> Result<Vec<u32>> {
+        let mut ids: Vec<u32> = self
+            .names()?
+            .iter()
+            .filter_map(|name| name.parse().ok())
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Start of the first and end of the last session of activity `id`, or `None` if it has no
+    /// sessions
+    fn recorded_range(&self, id: u32) -> Result<Option<(DateTime, DateTime)>> {
+        let activity = read_stored(self, id)?;
+        Ok(activity
+            .sessions
+            .first()
+            .zip(activity.sessions.last())
+            .map(|(first, last)| (first.start, last.end)))
+    }
+
+    /// Sessions of activity `id` that overlap the time range, with the index of the first of them
+    fn sessions_in_range(
+        &self,
+        id: u32,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<(usize, Vec<Session>)> {
+        let activity = read_stored(self, id)?;
+        let (i, j) = activity.get_in_range(from, to);
+        Ok((i, activity.sessions[i..j].to_vec()))
+    }
+
+    /// Number of sessions of activity `id` that overlap the time range, and the time spent in
+    /// them within it. This reads the whole activity, with no per-day totals, which only
+    /// `SqliteStorage` keeps
+    fn time_in_range(&self, id: u32, from: DateTime, to: DateTime) -> Result<(usize, Duration)> {
+        let activity = read_stored(self, id)?;
+        let (i, j) = activity.get_in_range(from, to);
+        Ok((j - i, activity.time_in_range(from, to)))
+    }
+}
+
+fn read_stored(storage: &(impl Storage + ?Sized), id: u32) -> Result<Activity> {
+    storage
+        .read_activity(id)?
+        .ok_or_else(|| anyhow!("Activity file {id} does not exist"))
+}
+
+/// Copies all data from one storage to another, returning the number of activities copied
+pub fn copy(from: &dyn Storage, to: &dyn Storage) -> Result<usize> {
+    for name in FILES {
+        if let Some(contents) = from.read(name)? {
+            to.write(name, &contents)?;
+        }
+    }
+    let ids = from.activity_ids()?;
+    for &id in &ids {
+        to.write_activity(id, &read_stored(from, id)?)?;
+    }
+    Ok(ids.len())
+}
+
+/// The user data directory, where track stores its data by default
+pub fn data_dir() -> Result<PathBuf> {
+    Ok(dirs::data_local_dir()
+        .ok_or_else(|| anyhow!("error: Failed to find user data directory"))?
+        .join("track"))
+}
+
+/// Storage in the user data directory: the SQLite database if the data has been migrated to it,
+/// and the files otherwise
+pub fn open() -> Result<Box<dyn Storage>> {
+    open_dir(data_dir()?)
+}
+
+/// Storage in `dir`, which is laid out like the user data directory
+pub fn open_dir(dir: impl Into<PathBuf>) -> Result<Box<dyn Storage>> {
+    let dir = dir.into();
+    if dir.join(DATABASE).exists() {
+        #[cfg(feature = "sqlite")]
+        return Ok(Box::new(SqliteStorage::open(dir.join(DATABASE))?));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(anyhow!(
+            "error: The data has been migrated to SQLite, which this build of track does not support"
+        ));
+    }
+    Ok(Box::new(FsStorage::new(dir)))
+}
+
+/// Name of the file in the data directory that is locked while a command changes the data
+pub const LOCK: &str = "track.lock";
+
+/// Exclusive use of the data in a directory, until dropped, so that commands run at the same time
+/// by different processes do not interleave their changes
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Waits until no other process is using the data in `dir`
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let file = Self::open(dir)?;
+        file.lock()?;
+        Ok(Self { _file: file })
+    }
+
+    /// Waits up to `timeout` for other processes to stop using the data in `dir`, returning
+    /// `None` if they have not
+    pub fn try_acquire(dir: &Path, timeout: time::Duration) -> Result<Option<Self>> {
+        let file = Self::open(dir)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Some(Self { _file: file })),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(time::Duration::from_millis(10))
+                }
+                Err(TryLockError::WouldBlock) => return Ok(None),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn open(dir: &Path) -> Result<File> {
+        fs::create_dir_all(dir)?;
+        Ok(File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK))?)
+    }
+}
+
+/// Storage in a directory of the filesystem
+pub struct FsStorage {
+    dir: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Storage in the user data directory, ignoring any SQLite database
+    pub fn user() -> Result<Self> {
+        Ok(Self::new(data_dir()?))
+    }
+}
+
+impl Storage for FsStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        if !self.dir.exists() {
+            fs::create_dir_all(&self.dir)?;
+        }
+        // Written whole and then renamed, so that commands reading without the lock never see a
+        // partly written file
+        let partial = self.dir.join(format!("{name}.partial"));
+        fs::write(&partial, contents)?;
+        fs::rename(partial, self.dir.join(name))?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        fs::remove_file(self.dir.join(name))?;
+        Ok(())
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries {
+                if let Some(name) = entry?.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// Storage that only lasts as long as it is kept, for tests and tools that should not touch the
+/// real data
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: RefCell<BTreeMap<String, Vec<u8>>>,
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.files.borrow().get(name).cloned())
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        self.files
+            .borrow_mut()
+            .insert(name.to_string(), contents.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.files
+            .borrow_mut()
+            .remove(name)
+            .map(drop)
+            .ok_or_else(|| anyhow!("No file named \"{name}\" is stored"))
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        Ok(self.files.borrow().keys().cloned().collect())
+    }
+}
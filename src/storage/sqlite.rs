@@ -0,0 +1,344 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use chrono::{Duration, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use super::Storage;
+use crate::{
+    data::{Activity, Session},
+    DateTime,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (
+        name TEXT PRIMARY KEY,
+        contents BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS activities (
+        id INTEGER PRIMARY KEY,
+        ongoing INTEGER,
+        notes TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+        activity INTEGER NOT NULL,
+        start INTEGER NOT NULL,
+        end INTEGER NOT NULL,
+        notes TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS sessions_by_end ON sessions (activity, end);
+";
+
+/// Time spent in sessions of each activity on each UTC day, kept up to date as sessions are
+/// written, so that statistics over long ranges do not read every session
+const DAILY: &str = "
+    CREATE TABLE daily (
+        activity INTEGER NOT NULL,
+        day INTEGER NOT NULL,
+        seconds INTEGER NOT NULL,
+        PRIMARY KEY (activity, day)
+    );
+";
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Storage in a SQLite database, where sessions are rows indexed by time, so that time ranges can
+/// be queried without reading whole activities
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < 1 {
+            let transaction = connection.unchecked_transaction()?;
+            transaction.execute_batch(DAILY)?;
+            {
+                let mut statement =
+                    transaction.prepare("SELECT activity, start, end FROM sessions")?;
+                let mut rows = statement.query([])?;
+                while let Some(row) = rows.next()? {
+                    add_daily(&transaction, row.get(0)?, row.get(1)?, row.get(2)?, 1)?;
+                }
+            }
+            transaction.execute_batch("PRAGMA user_version = 1")?;
+            transaction.commit()?;
+        }
+        if version < 2 {
+            let transaction = connection.unchecked_transaction()?;
+            transaction
+                .execute_batch("ALTER TABLE sessions ADD COLUMN id INTEGER NOT NULL DEFAULT 0")?;
+            {
+                let mut statement =
+                    transaction.prepare("SELECT rowid, start, end, notes FROM sessions")?;
+                let mut update =
+                    transaction.prepare("UPDATE sessions SET id = ?2 WHERE rowid = ?1")?;
+                let mut rows = statement.query([])?;
+                while let Some(row) = rows.next()? {
+                    let notes: String = row.get(3)?;
+                    let id = Session::derived_id(row.get(1)?, row.get(2)?, &notes);
+                    update.execute(params![row.get::<_, i64>(0)?, id as i64])?;
+                }
+            }
+            transaction.execute_batch("PRAGMA user_version = 2")?;
+            transaction.commit()?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// Time spent in sessions of activity `id` within the time range, clipping sessions to it
+    fn clipped(&self, id: u32, from: i64, to: i64) -> Result<i64> {
+        Ok(self.connection.query_row(
+            "SELECT COALESCE(SUM(MIN(end, ?3) - MAX(start, ?2)), 0) FROM sessions
+             WHERE activity = ?1 AND end > ?2 AND start < ?3",
+            params![id, from, to],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn sessions(&self, id: u32) -> Result<Vec<Session>> {
+        let mut statement = self.connection.prepare(
+            "SELECT start, end, notes, id FROM sessions WHERE activity = ?1 ORDER BY end",
+        )?;
+        let rows = statement.query_map([id], session_row)?;
+        to_sessions(rows)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT contents FROM files WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO files (name, contents) VALUES (?1, ?2)",
+            params![name, contents],
+        )?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM files WHERE name = ?1", [name])?;
+        Ok(())
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT name FROM files")?;
+        let names = statement.query_map([], |row| row.get(0))?;
+        Ok(names.collect::<Result<_, _>>()?)
+    }
+
+    fn read_activity(&self, id: u32) -> Result<Option<Activity>> {
+        let Some((ongoing, notes)) = self
+            .connection
+            .query_row(
+                "SELECT ongoing, notes FROM activities WHERE id = ?1",
+                [id],
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Activity {
+            ongoing: ongoing.map(from_timestamp).transpose()?,
+            sessions: self.sessions(id)?,
+            notes,
+        }))
+    }
+
+    /// Only inserts and deletes the sessions that changed since the activity was last written
+    fn write_activity(&self, id: u32, activity: &Activity) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO activities (id, ongoing, notes) VALUES (?1, ?2, ?3)",
+            params![
+                id,
+                activity.ongoing.map(|ongoing| ongoing.timestamp()),
+                activity.notes
+            ],
+        )?;
+        let mut stored: HashMap<(i64, i64, String, i64), Vec<i64>> = HashMap::new();
+        {
+            let mut statement = transaction
+                .prepare("SELECT rowid, start, end, notes, id FROM sessions WHERE activity = ?1")?;
+            let mut rows = statement.query([id])?;
+            while let Some(row) = rows.next()? {
+                stored
+                    .entry((row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+                    .or_default()
+                    .push(row.get(0)?);
+            }
+        }
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO sessions (activity, start, end, notes, id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for session in &activity.sessions {
+                let key = (
+                    session.start.timestamp(),
+                    session.end.timestamp(),
+                    session.notes.clone(),
+                    session.id as i64,
+                );
+                if stored.get_mut(&key).and_then(Vec::pop).is_none() {
+                    insert.execute(params![id, key.0, key.1, key.2, key.3])?;
+                    add_daily(&transaction, id, key.0, key.1, 1)?;
+                }
+            }
+            let mut delete = transaction.prepare("DELETE FROM sessions WHERE rowid = ?1")?;
+            for ((start, end, ..), rowids) in stored {
+                for rowid in rowids {
+                    delete.execute([rowid])?;
+                    add_daily(&transaction, id, start, end, -1)?;
+                }
+            }
+        }
+        transaction.execute(
+            "DELETE FROM daily WHERE activity = ?1 AND seconds = 0",
+            [id],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove_activity(&self, id: u32) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute("DELETE FROM sessions WHERE activity = ?1", [id])?;
+        transaction.execute("DELETE FROM daily WHERE activity = ?1", [id])?;
+        transaction.execute("DELETE FROM activities WHERE id = ?1", [id])?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn activity_ids(&self) -> Result<Vec<u32>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM activities ORDER BY id")?;
+        let ids = statement.query_map([], |row| row.get(0))?;
+        Ok(ids.collect::<Result<_, _>>()?)
+    }
+
+    fn recorded_range(&self, id: u32) -> Result<Option<(DateTime, DateTime)>> {
+        // Sessions of an activity do not overlap, so the first to end is also the first to start
+        let first = self
+            .connection
+            .query_row(
+                "SELECT start FROM sessions WHERE activity = ?1 ORDER BY end LIMIT 1",
+                [id],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        let last = self
+            .connection
+            .query_row(
+                "SELECT end FROM sessions WHERE activity = ?1 ORDER BY end DESC LIMIT 1",
+                [id],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        match first.zip(last) {
+            Some((first, last)) => Ok(Some((from_timestamp(first)?, from_timestamp(last)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn sessions_in_range(
+        &self,
+        id: u32,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<(usize, Vec<Session>)> {
+        let (from, to) = (from.timestamp(), to.timestamp());
+        let before: usize = self.connection.query_row(
+            "SELECT COUNT(*) FROM sessions WHERE activity = ?1 AND end <= ?2",
+            params![id, from],
+            |row| row.get(0),
+        )?;
+        let mut statement = self.connection.prepare(
+            "SELECT start, end, notes, id FROM sessions
+             WHERE activity = ?1 AND end > ?2 AND start < ?3 ORDER BY end",
+        )?;
+        let rows = statement.query_map(params![id, from, to], session_row)?;
+        Ok((before, to_sessions(rows)?))
+    }
+
+    fn time_in_range(&self, id: u32, from: DateTime, to: DateTime) -> Result<(usize, Duration)> {
+        let (from, to) = (from.timestamp(), to.timestamp());
+        let sessions: usize = self.connection.query_row(
+            "SELECT COUNT(*) FROM sessions WHERE activity = ?1 AND end > ?2 AND start < ?3",
+            params![id, from, to],
+            |row| row.get(0),
+        )?;
+        // Whole days in the range come from the daily totals, and the partial days at either end
+        // from the sessions
+        let (first_day, last_day) = ((from + DAY - 1).div_euclid(DAY), to.div_euclid(DAY));
+        let seconds = if first_day < last_day {
+            let whole: i64 = self.connection.query_row(
+                "SELECT COALESCE(SUM(seconds), 0) FROM daily
+                 WHERE activity = ?1 AND day >= ?2 AND day < ?3",
+                params![id, first_day, last_day],
+                |row| row.get(0),
+            )?;
+            self.clipped(id, from, first_day * DAY)?
+                + whole
+                + self.clipped(id, last_day * DAY, to)?
+        } else {
+            self.clipped(id, from, to)?
+        };
+        Ok((sessions, Duration::seconds(seconds)))
+    }
+}
+
+/// Adds the time of the session from `start` to `end` to the daily totals of activity `id`, or
+/// subtracts it if `sign` is -1
+fn add_daily(connection: &Connection, id: u32, start: i64, end: i64, sign: i64) -> Result<()> {
+    let mut day = start.div_euclid(DAY);
+    while day * DAY < end && start < end {
+        let seconds = end.min((day + 1) * DAY) - start.max(day * DAY);
+        connection.execute(
+            "INSERT INTO daily (activity, day, seconds) VALUES (?1, ?2, ?3)
+             ON CONFLICT (activity, day) DO UPDATE SET seconds = seconds + excluded.seconds",
+            params![id, day, seconds * sign],
+        )?;
+        day += 1;
+    }
+    Ok(())
+}
+
+fn session_row(row: &Row) -> rusqlite::Result<(i64, i64, String, i64)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn to_sessions(
+    rows: impl Iterator<Item = rusqlite::Result<(i64, i64, String, i64)>>,
+) -> Result<Vec<Session>> {
+    rows.map(|row| {
+        let (start, end, notes, id) = row?;
+        Ok(Session {
+            start: from_timestamp(start)?,
+            end: from_timestamp(end)?,
+            notes,
+            id: id as u64,
+        })
+    })
+    .collect()
+}
+
+fn from_timestamp(seconds: i64) -> Result<DateTime> {
+    Utc.timestamp_opt(seconds, 0)
+        .single()
+        .ok_or_else(|| anyhow!("Invalid timestamp {seconds} in database"))
+}
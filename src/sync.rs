@@ -0,0 +1,415 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+};
+
+use anyhow::{anyhow, Result};
+use bincode::{deserialize, serialize};
+use chrono::serde::ts_seconds_option;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{Activity, ActivityInfo, Data, Session},
+    storage::Storage,
+    DateTime,
+};
+
+/// What changed when syncing, and what needs to be resolved manually
+pub struct Report {
+    /// Number of changes made to the local data
+    pub received: usize,
+    /// Number of changes made to the other data
+    pub sent: usize,
+    pub conflicts: Vec<String>,
+    pub overlaps: Vec<String>,
+}
+
+/// Activities by name, as they are synced. The snapshot taken at the last sync is stored with the
+/// local data, and the changes on each side since then are found by comparing against it
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct Snapshot {
+    activities: BTreeMap<String, Synced>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+struct Synced {
+    archived: bool,
+    #[serde(with = "ts_seconds_option")]
+    ongoing: Option<DateTime>,
+    notes: String,
+    sessions: BTreeMap<u64, Session>,
+}
+
+impl Snapshot {
+    fn read(storage: &dyn Storage) -> Result<Self> {
+        Ok(match storage.read("sync")? {
+            Some(encoded) => deserialize(&encoded)?,
+            None => Self::default(),
+        })
+    }
+
+    fn write(&self, storage: &dyn Storage) -> Result<()> {
+        storage.write("sync", &serialize(self)?)
+    }
+
+    fn collect(storage: &dyn Storage) -> Result<Self> {
+        let data = Data::read(storage)?;
+        let mut activities = BTreeMap::new();
+        for info in &data.activities {
+            let activity = Activity::read(storage, info.id)?;
+            let synced = Synced {
+                archived: data.is_archived(info.id),
+                ongoing: activity.ongoing,
+                notes: activity.notes,
+                sessions: activity
+                    .sessions
+                    .into_iter()
+                    .map(|session| (session.id, session))
+                    .collect(),
+            };
+            activities.insert(info.name.clone(), synced);
+        }
+        Ok(Self { activities })
+    }
+
+    /// Writes the activities that differ from `before`, which is what `storage` holds, returning
+    /// the number of changes. `names` are the names in this snapshot of the activities in
+    /// `before` that are kept, by their names in `before`
+    fn apply(
+        &self,
+        storage: &dyn Storage,
+        before: &Snapshot,
+        names: &BTreeMap<String, String>,
+    ) -> Result<usize> {
+        let mut data = Data::read(storage)?;
+        let mut changes = 0;
+        let position = |data: &Data, name: &str| {
+            data.activities
+                .iter()
+                .position(|info| info.name == name)
+                .ok_or_else(|| anyhow!("error: \"{name}\" changed while syncing, sync again"))
+        };
+        for name in before.activities.keys() {
+            if !names.contains_key(name) {
+                let i = position(&data, name)?;
+                data.delete(storage, i)?;
+                changes += 1;
+            }
+        }
+        let renames: Vec<_> = names.iter().filter(|(from, to)| from != to).collect();
+        let indices = renames
+            .iter()
+            .map(|(from, _)| position(&data, from))
+            .collect::<Result<Vec<_>>>()?;
+        for (i, (_, to)) in indices.into_iter().zip(renames) {
+            let info = &mut data.activities[i];
+            info.name = to.clone();
+            if data
+                .active
+                .as_ref()
+                .is_some_and(|active| active.id == info.id)
+            {
+                data.active = Some(info.clone());
+            }
+            changes += 1;
+        }
+        let old_names: BTreeMap<_, _> = names.iter().map(|(from, to)| (to, from)).collect();
+        for (name, synced) in &self.activities {
+            let old = old_names
+                .get(name)
+                .and_then(|old_name| before.activities.get(*old_name));
+            if old == Some(synced) {
+                continue;
+            }
+            changes += count_changes(old, synced);
+            let id = match data.activities.iter().find(|info| &info.name == name) {
+                Some(info) => info.id,
+                None => {
+                    let id = data.next_id();
+                    data.activities.push(ActivityInfo::new(name.clone(), id));
+                    id
+                }
+            };
+            data.archived.retain(|&other| other != id);
+            if synced.archived {
+                data.archived.push(id);
+                if data.active.as_ref().is_some_and(|info| info.id == id) {
+                    data.active = None;
+                }
+            }
+            let mut sessions: Vec<_> = synced.sessions.values().cloned().collect();
+            sessions.sort_by_key(|session| session.start);
+            let activity = Activity {
+                ongoing: synced.ongoing,
+                sessions,
+                notes: synced.notes.clone(),
+            };
+            activity.write(storage, id)?;
+        }
+        data.write(storage)?;
+        Ok(changes)
+    }
+
+    /// Gives activities that were renamed since `base` their names in `base` again, returning
+    /// their new names by their names in `base`. An activity is taken to have been renamed from a
+    /// name that is no longer used if its own name is new, and it has a session that the activity
+    /// with the old name had
+    fn follow_renames(&mut self, base: &Snapshot) -> BTreeMap<String, String> {
+        let mut renames = BTreeMap::new();
+        for (old, synced) in &base.activities {
+            if self.activities.contains_key(old) {
+                continue;
+            }
+            let renamed = self.activities.iter().find(|(name, activity)| {
+                !base.activities.contains_key(*name)
+                    && !renames.values().any(|new| new == *name)
+                    && activity
+                        .sessions
+                        .keys()
+                        .any(|id| synced.sessions.contains_key(id))
+            });
+            if let Some((new, _)) = renamed {
+                renames.insert(old.clone(), new.clone());
+            }
+        }
+        for (old, new) in &renames {
+            let synced = self.activities.remove(new).unwrap();
+            self.activities.insert(old.clone(), synced);
+        }
+        renames
+    }
+
+    /// Descriptions of the sessions that overlap another session of the same activity
+    fn overlaps(&self) -> Vec<String> {
+        let mut overlaps = Vec::new();
+        for (name, synced) in &self.activities {
+            let mut sessions: Vec<_> = synced.sessions.values().collect();
+            sessions.sort_by_key(|session| session.start);
+            for pair in sessions.windows(2) {
+                if pair[1].start < pair[0].end {
+                    overlaps.push(format!(
+                        "Sessions of \"{name}\" overlap: {} and {}",
+                        pair[0], pair[1]
+                    ));
+                }
+            }
+        }
+        overlaps
+    }
+}
+
+/// Number of sessions and other details of an activity that differ between `old` and `new`
+fn count_changes(old: Option<&Synced>, new: &Synced) -> usize {
+    let Some(old) = old else {
+        return 1 + new.sessions.len();
+    };
+    let ids: BTreeSet<_> = old.sessions.keys().chain(new.sessions.keys()).collect();
+    let sessions = ids
+        .into_iter()
+        .filter(|id| old.sessions.get(id) != new.sessions.get(id))
+        .count();
+    let details =
+        old.archived != new.archived || (old.ongoing, &old.notes) != (new.ongoing, &new.notes);
+    sessions + details as usize
+}
+
+/// Result of merging a value that may have changed on either side since the last sync
+enum Merge<T> {
+    Agreed(Option<T>),
+    Conflict,
+}
+
+fn merge<T: PartialEq + Clone>(base: Option<&T>, ours: Option<&T>, theirs: Option<&T>) -> Merge<T> {
+    if ours == theirs || theirs == base {
+        Merge::Agreed(ours.cloned())
+    } else if ours == base {
+        Merge::Agreed(theirs.cloned())
+    } else {
+        Merge::Conflict
+    }
+}
+
+/// Merges the local data with the data of `other`, writing the result to both, and returns what
+/// changed and what needs to be resolved manually. Activities are matched by name, or by the ids
+/// of their sessions if they were renamed since the last sync, and sessions by id. An activity
+/// renamed without any sessions is synced as a new activity. Settings and idle intervals are not
+/// synced, as they belong to each machine
+pub fn sync(local: &dyn Storage, other: &dyn Storage) -> Result<Report> {
+    let base = Snapshot::read(local)?;
+    let (collected_ours, collected_theirs) = (Snapshot::collect(local)?, Snapshot::collect(other)?);
+    let (mut ours, mut theirs) = (collected_ours.clone(), collected_theirs.clone());
+    let our_renames = ours.follow_renames(&base);
+    let their_renames = theirs.follow_renames(&base);
+    let mut conflicts = Vec::new();
+    let mut merged = Snapshot::default();
+    let names: BTreeSet<_> = base
+        .activities
+        .keys()
+        .chain(ours.activities.keys())
+        .chain(theirs.activities.keys())
+        .collect();
+    for name in names {
+        let base = base.activities.get(name);
+        let (ours, theirs) = (ours.activities.get(name), theirs.activities.get(name));
+        let synced = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => {
+                Some(merge_activity(name, base, ours, theirs, &mut conflicts))
+            }
+            _ => match merge(base, ours, theirs) {
+                Merge::Agreed(synced) => synced,
+                Merge::Conflict => {
+                    let (kept, problem) = match ours {
+                        Some(ours) => (ours, "changed here but removed in the other data"),
+                        None => (
+                            theirs.unwrap(),
+                            "removed here but changed in the other data",
+                        ),
+                    };
+                    conflicts.push(format!("\"{name}\" was {problem}, so it was kept"));
+                    Some(kept.clone())
+                }
+            },
+        };
+        if let Some(synced) = synced {
+            merged.activities.insert(name.clone(), synced);
+        }
+    }
+    let finals = renamed(&merged, &our_renames, &their_renames, &mut conflicts);
+    let names = |collected: &Snapshot, renames: &BTreeMap<String, String>| {
+        let base_names: BTreeMap<_, _> = renames.iter().map(|(old, new)| (new, old)).collect();
+        collected
+            .activities
+            .keys()
+            .filter_map(|name| {
+                let base_name = base_names.get(name).copied().unwrap_or(name);
+                merged.activities.contains_key(base_name).then(|| {
+                    let new = finals.get(base_name).unwrap_or(base_name);
+                    (name.clone(), new.clone())
+                })
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (our_names, their_names) = (
+        names(&collected_ours, &our_renames),
+        names(&collected_theirs, &their_renames),
+    );
+    merged.activities = mem::take(&mut merged.activities)
+        .into_iter()
+        .map(|(name, synced)| (finals.get(&name).cloned().unwrap_or(name), synced))
+        .collect();
+    let report = Report {
+        received: merged.apply(local, &collected_ours, &our_names)?,
+        sent: merged.apply(other, &collected_theirs, &their_names)?,
+        conflicts,
+        overlaps: merged.overlaps(),
+    };
+    merged.write(local)?;
+    Ok(report)
+}
+
+/// The names that activities renamed on either side get, by their names in the last sync. An
+/// activity renamed differently on both sides gets our name, and one renamed to a name that is
+/// already used keeps its name
+fn renamed(
+    merged: &Snapshot,
+    ours: &BTreeMap<String, String>,
+    theirs: &BTreeMap<String, String>,
+    conflicts: &mut Vec<String>,
+) -> BTreeMap<String, String> {
+    let mut finals = BTreeMap::new();
+    for name in merged.activities.keys() {
+        let new = match (ours.get(name), theirs.get(name)) {
+            (Some(ours), Some(theirs)) if ours != theirs => {
+                conflicts.push(format!(
+                    "\"{name}\" was renamed to \"{ours}\" here and to \"{theirs}\" in the other \
+                     data, so it was named \"{ours}\""
+                ));
+                ours
+            }
+            (Some(new), _) | (None, Some(new)) => new,
+            (None, None) => continue,
+        };
+        if merged.activities.contains_key(new) || finals.values().any(|name| name == new) {
+            conflicts.push(format!(
+                "\"{name}\" was renamed to \"{new}\", but another activity has that name, so it \
+                 kept its name"
+            ));
+        } else {
+            finals.insert(name.clone(), new.clone());
+        }
+    }
+    finals
+}
+
+fn merge_activity(
+    name: &str,
+    base: Option<&Synced>,
+    ours: &Synced,
+    theirs: &Synced,
+    conflicts: &mut Vec<String>,
+) -> Synced {
+    let empty = Synced::default();
+    let base = base.unwrap_or(&empty);
+    let archived = match merge(
+        Some(&base.archived),
+        Some(&ours.archived),
+        Some(&theirs.archived),
+    ) {
+        Merge::Agreed(archived) => archived.unwrap(),
+        Merge::Conflict => ours.archived,
+    };
+    let ongoing = |synced: &Synced| (synced.ongoing, synced.notes.clone());
+    let (ongoing, notes) = match merge(
+        Some(&ongoing(base)),
+        Some(&ongoing(ours)),
+        Some(&ongoing(theirs)),
+    ) {
+        Merge::Agreed(ongoing) => ongoing.unwrap(),
+        Merge::Conflict => {
+            conflicts.push(format!(
+                "The ongoing session of \"{name}\" changed on both sides, so this side's was kept"
+            ));
+            ongoing(ours)
+        }
+    };
+    let mut sessions = BTreeMap::new();
+    let ids: BTreeSet<_> = base
+        .sessions
+        .keys()
+        .chain(ours.sessions.keys())
+        .chain(theirs.sessions.keys())
+        .collect();
+    for id in ids {
+        let (ours, theirs) = (ours.sessions.get(id), theirs.sessions.get(id));
+        let session = match merge(base.sessions.get(id), ours, theirs) {
+            Merge::Agreed(session) => session,
+            Merge::Conflict => {
+                let (kept, problem) = match (ours, theirs) {
+                    (Some(ours), Some(theirs)) => (
+                        ours,
+                        format!("was edited on both sides, so this side's version was kept instead of {theirs}"),
+                    ),
+                    (Some(ours), None) => (
+                        ours,
+                        "was edited here but removed in the other data, so it was kept".to_string(),
+                    ),
+                    (None, theirs) => (
+                        theirs.unwrap(),
+                        "was removed here but edited in the other data, so it was kept".to_string(),
+                    ),
+                };
+                conflicts.push(format!("Session {kept} of \"{name}\" {problem}"));
+                Some(kept.clone())
+            }
+        };
+        if let Some(session) = session {
+            sessions.insert(*id, session);
+        }
+    }
+    Synced {
+        archived,
+        ongoing,
+        notes,
+        sessions,
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{at, session, Env};
+use track::{
+    commands,
+    data::{Activity, Data},
+    Bound, Position,
+};
+
+fn archived_env() -> Env {
+    let env = Env::with_activity();
+    commands::create(&env.ctx(), "old".to_string()).unwrap();
+    commands::archive(&env.ctx(), "old".to_string()).unwrap();
+    commands::set(&env.ctx(), "work".to_string()).unwrap();
+    env.add("20/06/24-10:00", "20/06/24-11:00", "").unwrap();
+    env
+}
+
+fn old_sessions(env: &Env) -> usize {
+    let data = Data::read(&env.storage).unwrap();
+    let info = data
+        .activities
+        .iter()
+        .find(|info| info.name == "old")
+        .unwrap();
+    Activity::read(&env.storage, info.id)
+        .unwrap()
+        .sessions
+        .len()
+}
+
+#[test]
+fn sessions_cannot_be_moved_into_archived_activities() {
+    let env = archived_env();
+    let e =
+        commands::merge(&env.ctx_with("y\n"), "work".to_string(), "old".to_string()).unwrap_err();
+    assert_eq!(e.to_string(), "error: \"old\" is archived");
+    for keep in [false, true] {
+        let positions = vec![Position::Index(1)];
+        let result = if keep {
+            commands::copy(&env.ctx(), positions, "old".to_string())
+        } else {
+            commands::move_sessions(&env.ctx(), positions, "old".to_string())
+        };
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "error: \"old\" is archived"
+        );
+    }
+    assert_eq!(old_sessions(&env), 0);
+    assert_eq!(
+        env.sessions(),
+        [session(at(20, "10:00"), at(20, "11:00"), "")]
+    );
+    assert_eq!(
+        Data::read(&env.storage).unwrap().active.unwrap().name,
+        "work"
+    );
+}
+
+#[test]
+fn gaps_cannot_be_assigned_to_archived_activities() {
+    let env = archived_env();
+    env.add("20/06/24-09:00", "20/06/24-09:30", "").unwrap();
+    commands::gaps(
+        &env.ctx_with("old\nwork\nfilled\n"),
+        Bound::None,
+        Bound::None,
+        1,
+        true,
+    )
+    .unwrap();
+    assert_eq!(old_sessions(&env), 0);
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(20, "09:00"), at(20, "09:30"), ""),
+            session(at(20, "09:30"), at(20, "10:00"), "filled"),
+            session(at(20, "10:00"), at(20, "11:00"), ""),
+        ]
+    );
+}
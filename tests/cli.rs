@@ -0,0 +1,187 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use track::storage::Lock;
+
+/// Runs track with `args` and an empty data directory, returning what it printed
+fn track(args: &[&str]) -> String {
+    track_in(&env::temp_dir().join("track-cli-empty"), args)
+}
+
+/// Runs track with `args` and the data directory in `home`, returning what it printed. Fails if
+/// track takes longer than a few seconds, e.g. because it is waiting for the lock
+fn track_in(home: &Path, args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_track"))
+        .args(args)
+        .env("XDG_DATA_HOME", home)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("track {args:?} did not finish");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "track {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// A data home for one test, removed when dropped
+struct Home(PathBuf);
+
+impl Home {
+    fn new(name: &str) -> Self {
+        let home = env::temp_dir().join(format!("track-cli-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(home.join("track")).unwrap();
+        Self(home)
+    }
+
+    fn data(&self) -> PathBuf {
+        self.0.join("track")
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+const NO_STATUS: &str =
+    r#"{"activity":null,"elapsed":null,"notes":null,"ongoing":false,"start":null,"today":null}"#;
+
+#[test]
+fn offsets_with_non_ascii_units_are_rejected() {
+    for shift in ["1é", "-2ü", "+3h1é"] {
+        let output = track(&["edit", "1", "--shift", shift]);
+        assert!(output.contains("offset must be in the form [+|-][Nw][Nd][Nh][Nm]"));
+    }
+}
+
+#[test]
+fn durations_too_long_to_hold_are_rejected() {
+    let output = track(&["timer", "9999999999999999w"]);
+    assert!(output.contains("length must be in the form [Nw][Nd][Nh][Nm]"));
+    let output = track(&["edit", "1", "--shift", "99999999999999w"]);
+    assert!(output.contains("offset must be in the form [+|-][Nw][Nd][Nh][Nm]"));
+}
+
+#[test]
+fn status_does_not_wait_for_the_lock() {
+    let home = Home::new("status-lock");
+    track_in(&home.0, &["new", "work"]);
+    let _lock = Lock::acquire(&home.data()).unwrap();
+    let output = track_in(&home.0, &["status", "--json"]);
+    assert!(output.starts_with(r#"{"activity":"work","#));
+}
+
+#[test]
+fn status_is_empty_when_the_data_cannot_be_opened() {
+    let home = Home::new("status-broken");
+    fs::write(home.data().join("track.db"), "not a database").unwrap();
+    let output = track_in(&home.0, &["status", "--json"]);
+    assert_eq!(output.trim_end(), NO_STATUS);
+    assert_eq!(track_in(&home.0, &["status"]), "");
+}
+
+#[test]
+fn commands_print_what_they_did() {
+    let home = Home::new("print");
+    let output = track_in(&home.0, &["new", "work"]);
+    assert_eq!(
+        output,
+        "Created new activity \"work\"\n\"work\" is now active\n"
+    );
+    track_in(&home.0, &["new", "play"]);
+    assert!(track_in(&home.0, &["start"]).starts_with("Started new session of \"play\""));
+    let output = track_in(&home.0, &["switch", "work"]);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines[..2], ["Ended session of \"play\"", "New session:"]);
+    assert_eq!(lines[3], "\"work\" is now active");
+    assert!(lines[4].starts_with("Started new session of \"work\""));
+    assert_eq!(
+        track_in(&home.0, &["cancel"]),
+        "Cancelled ongoing session of \"work\"\n"
+    );
+}
+
+#[test]
+fn prompts_do_not_hold_the_lock() {
+    let home = Home::new("prompt");
+    track_in(&home.0, &["new", "play"]);
+    track_in(&home.0, &["new", "work"]);
+    let mut merge = Command::new(env!("CARGO_BIN_EXE_track"))
+        .args(["merge", "play", "work"])
+        .env("XDG_DATA_HOME", &home.0)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = merge.stdout.take().unwrap();
+    let mut output = Vec::new();
+    while !output.ends_with(b"Enter \"y\" if so: ") {
+        let mut byte = [0];
+        assert_eq!(stdout.read(&mut byte).unwrap(), 1, "track merge exited");
+        output.push(byte[0]);
+    }
+    track_in(&home.0, &["new", "other"]);
+    merge.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    stdout.read_to_end(&mut output).unwrap();
+    merge.wait().unwrap();
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .ends_with("The data was changed by another track command while waiting for an answer, run the command again"));
+    let output = track_in(&home.0, &["all"]);
+    assert!(output.contains("play") && output.contains("other"));
+}
+
+#[test]
+fn reading_does_not_wait_for_the_lock() {
+    let home = Home::new("read-lock");
+    track_in(&home.0, &["new", "work"]);
+    let _lock = Lock::acquire(&home.data()).unwrap();
+    assert!(track_in(&home.0, &["all"]).contains("work"));
+    track_in(&home.0, &["view"]);
+    track_in(&home.0, &["config", "work-start"]);
+}
+
+#[test]
+fn sync_waits_for_the_lock_of_the_other_directory() {
+    let home = Home::new("sync-lock");
+    track_in(&home.0, &["new", "work"]);
+    let shared = home.0.join("shared");
+    fs::create_dir_all(&shared).unwrap();
+    let lock = Lock::acquire(&shared).unwrap();
+    let started = Instant::now();
+    let release = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        drop(lock);
+    });
+    let output = track_in(&home.0, &["sync", shared.to_str().unwrap()]);
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert!(output.starts_with("Received 0 changes"));
+    release.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn hook_commands_can_run_track() {
+    let home = Home::new("hook");
+    track_in(&home.0, &["new", "work"]);
+    let command = format!("{} new hooked", env!("CARGO_BIN_EXE_track"));
+    track_in(&home.0, &["config", "hook-command", &command]);
+    track_in(&home.0, &["start"]);
+    assert!(track_in(&home.0, &["all"]).contains("hooked"));
+}
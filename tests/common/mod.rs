@@ -0,0 +1,87 @@
+#![allow(dead_code)]
+
+use chrono::{NaiveDate, NaiveTime};
+use track::{
+    clock::FixedClock,
+    commands::{self, Context},
+    data::{Activity, Data},
+    parse_dt,
+    storage::MemoryStorage,
+    Absolute, Bound, DateTime, Filter, Position,
+};
+
+/// In-memory data and a fixed clock, set to 12:00 on 20/06/24
+pub struct Env {
+    pub storage: MemoryStorage,
+    pub clock: FixedClock,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self {
+            storage: MemoryStorage::default(),
+            clock: FixedClock::new(at(20, "12:00")),
+        }
+    }
+
+    /// An environment with an active activity named "work"
+    pub fn with_activity() -> Self {
+        let env = Self::new();
+        commands::create(&env.ctx(), "work".to_string()).unwrap();
+        env
+    }
+
+    /// A context that answers prompts with no input
+    pub fn ctx(&self) -> Context<'_> {
+        self.ctx_with("")
+    }
+
+    /// A context that answers prompts with `input`
+    pub fn ctx_with<'a>(&'a self, input: &'a str) -> Context<'a> {
+        Context::new(&self.storage, &self.clock, input.as_bytes())
+    }
+
+    pub fn add(&self, start: &str, end: &str, notes: &str) -> anyhow::Result<()> {
+        commands::add(&self.ctx(), abs(start), abs(end), notes.to_string())
+    }
+
+    pub fn current(&self) -> Activity {
+        Data::read(&self.storage)
+            .unwrap()
+            .read_current(&self.storage)
+            .unwrap()
+            .0
+    }
+
+    /// The sessions of the active activity, as (start, end, notes)
+    pub fn sessions(&self) -> Vec<(DateTime, DateTime, String)> {
+        self.current()
+            .sessions
+            .into_iter()
+            .map(|session| (session.start, session.end, session.notes))
+            .collect()
+    }
+}
+
+/// A local time in June 2024, which has no daylight saving changes in most time zones
+pub fn at(day: u32, time: &str) -> DateTime {
+    let date = NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+    parse_dt(date.and_time(NaiveTime::parse_from_str(time, "%R").unwrap()))
+}
+
+pub fn abs(s: &str) -> Absolute {
+    Absolute::parse(s).unwrap()
+}
+
+pub fn positions(positions: &[usize]) -> Filter {
+    Filter {
+        positions: positions.iter().map(|&i| Position::Index(i)).collect(),
+        from: Bound::None,
+        to: Bound::None,
+        notes: None,
+    }
+}
+
+pub fn session(start: DateTime, end: DateTime, notes: &str) -> (DateTime, DateTime, String) {
+    (start, end, notes.to_string())
+}
@@ -0,0 +1,158 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{env, fs, path::PathBuf, process};
+
+use chrono::Duration;
+use common::Env;
+use serde_json::Value;
+use track::{clock::Clock, commands};
+
+/// An environment whose hook command appends each event to a file, as one line of JSON
+struct Hooked {
+    env: Env,
+    log: PathBuf,
+}
+
+impl Hooked {
+    fn new(name: &str) -> Self {
+        let log = env::temp_dir().join(format!("track-hooks-{name}-{}", process::id()));
+        let _ = fs::remove_file(&log);
+        let env = Env::new();
+        let command = format!(
+            "cat >> {0} && echo \" $TRACK_EVENT $TRACK_ACTIVITY\" >> {0}",
+            log.display()
+        );
+        set(&env, "hook-command", &command);
+        Self { env, log }
+    }
+
+    /// The events so far, as their JSON and the environment variables TRACK_EVENT and
+    /// TRACK_ACTIVITY
+    fn events(&self) -> Vec<(Value, String)> {
+        let log = fs::read_to_string(&self.log).unwrap_or_default();
+        log.lines()
+            .map(|line| {
+                let end = line.rfind('}').unwrap() + 1;
+                (
+                    serde_json::from_str(&line[..end]).unwrap(),
+                    line[end..].trim().to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Drop for Hooked {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.log);
+    }
+}
+
+fn set(env: &Env, key: &str, value: &str) {
+    commands::config(
+        &env.ctx(),
+        Some(key.to_string()),
+        Some(value.to_string()),
+        false,
+    )
+    .unwrap();
+}
+
+#[test]
+fn commands_run_the_hook_command() {
+    let hooked = Hooked::new("commands");
+    let env = &hooked.env;
+    commands::create(&env.ctx(), "work".to_string()).unwrap();
+    commands::create(&env.ctx(), "play".to_string()).unwrap();
+    commands::set(&env.ctx(), "work".to_string()).unwrap();
+    commands::start(&env.ctx()).unwrap();
+    env.clock.advance(Duration::hours(1));
+    commands::switch(&env.ctx(), "play".to_string(), "done".to_string()).unwrap();
+    commands::cancel(&env.ctx()).unwrap();
+    commands::rename(&env.ctx(), "play".to_string(), "rest".to_string()).unwrap();
+    commands::delete(&env.ctx_with("y\n"), "rest".to_string()).unwrap();
+
+    let events = hooked.events();
+    let names: Vec<_> = events.iter().map(|(_, vars)| vars.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "create work",
+            "create play",
+            "start work",
+            "end work",
+            "start play",
+            "switch play",
+            "cancel play",
+            "rename rest",
+            "delete rest",
+        ]
+    );
+    let (end, _) = &events[3];
+    assert_eq!(end["notes"], "done");
+    assert_eq!(end["end"], env.clock.now().to_rfc3339());
+    assert_eq!(events[5].0["from"], "work");
+    assert_eq!(events[7].0["from"], "play");
+}
+
+#[test]
+fn hook_events_limit_what_runs_the_hook_command() {
+    let hooked = Hooked::new("events");
+    let env = &hooked.env;
+    set(env, "hook-events", "start, end");
+    commands::create(&env.ctx(), "work".to_string()).unwrap();
+    commands::start(&env.ctx()).unwrap();
+    commands::cancel(&env.ctx()).unwrap();
+    let events = hooked.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].1, "start work");
+
+    let e = commands::config(
+        &env.ctx(),
+        Some("hook-events".to_string()),
+        Some("start,stop".to_string()),
+        false,
+    )
+    .unwrap_err();
+    assert!(e.to_string().contains("\"stop\" is not one of"));
+}
+
+#[test]
+fn failing_hook_command_does_not_stop_commands() {
+    let env = Env::new();
+    set(&env, "hook-command", "exit 1");
+    commands::create(&env.ctx(), "work".to_string()).unwrap();
+    commands::start(&env.ctx()).unwrap();
+    assert!(env.current().ongoing.is_some());
+}
+
+#[test]
+fn reaching_the_daily_goal_runs_the_hook_command() {
+    let hooked = Hooked::new("goal");
+    let env = &hooked.env;
+    set(env, "daily-goal", "2h");
+    commands::create(&env.ctx(), "work".to_string()).unwrap();
+    env.add("20/06/24-09:00", "20/06/24-10:00", "").unwrap();
+    for _ in 0..2 {
+        commands::start(&env.ctx()).unwrap();
+        env.clock.advance(Duration::hours(1));
+        commands::end(&env.ctx(), String::new()).unwrap();
+    }
+    let events = hooked.events();
+    let names: Vec<_> = events.iter().map(|(_, vars)| vars.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "create work",
+            "start work",
+            "end work",
+            "goal work",
+            "start work",
+            "end work",
+        ]
+    );
+    assert_eq!(events[3].0["goal"], 7200);
+    assert_eq!(events[3].0["today"], 7200);
+}
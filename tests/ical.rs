@@ -0,0 +1,176 @@
+mod common;
+
+use std::{env, fs, process};
+
+use common::{abs, at, session, Env};
+use track::{commands, data::Session, ical, Bound};
+
+/// Imports `calendar` into the active activity through a temporary file
+fn import(env: &Env, name: &str, calendar: &str) -> anyhow::Result<()> {
+    let file = env::temp_dir().join(format!("track-ical-{name}-{}.ics", process::id()));
+    fs::write(&file, calendar.replace('\n', "\r\n")).unwrap();
+    let result = commands::import(&env.ctx(), file.clone());
+    fs::remove_file(file).unwrap();
+    result
+}
+
+#[test]
+fn exported_sessions_can_be_read_back() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    let notes = "planning, review; and a note long enough that its line has to be folded";
+    env.add("11/06/24-09:00", "11/06/24-11:30", notes).unwrap();
+    let activity = env.current();
+    let calendar = ical::write(
+        activity.sessions.iter().map(|session| ("work", session)),
+        at(20, "12:00"),
+    );
+    assert!(calendar.lines().all(|line| line.len() <= 75));
+    for session in &activity.sessions {
+        assert!(calendar.contains(&format!("UID:{}@track\r\n", session.full_id())));
+    }
+
+    let events: Vec<_> = ical::read(&calendar)
+        .unwrap()
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        (events[0].start, events[0].end, events[0].summary.as_str()),
+        (at(10, "09:00"), at(10, "10:00"), "work")
+    );
+    assert_eq!(events[0].description, None);
+    assert_eq!(events[1].description.as_deref(), Some(notes));
+}
+
+#[test]
+fn export_writes_sessions_in_range() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "").unwrap();
+    let file = env::temp_dir().join(format!("track-ical-export-{}.ics", process::id()));
+    commands::export(
+        &env.ctx(),
+        Bound::Absolute(abs("11/06/24")),
+        Bound::None,
+        None,
+        Some(file.clone()),
+    )
+    .unwrap();
+    let calendar = fs::read_to_string(&file).unwrap();
+    fs::remove_file(file).unwrap();
+    let events = ical::read(&calendar).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].as_ref().unwrap().start, at(12, "09:00"));
+}
+
+#[test]
+fn import_adds_events_as_sessions() {
+    let env = Env::with_activity();
+    let standup = Session::new(at(10, "09:00"), at(10, "10:00"), String::new());
+    let calendar = ical::write([("Standup, team", &standup)], at(20, "12:00"));
+    import(&env, "write", &calendar).unwrap();
+    import(
+        &env,
+        "duration",
+        "BEGIN:VCALENDAR
+BEGIN:VEVENT
+DTSTART:20240611T090000
+DURATION:PT1H30M
+SUMMARY:Planning
+BEGIN:VALARM
+DESCRIPTION:Reminder
+END:VALARM
+END:VEVENT
+END:VCALENDAR
+",
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(10, "09:00"), at(10, "10:00"), "Standup, team"),
+            session(at(11, "09:00"), at(11, "10:30"), "Planning"),
+        ]
+    );
+}
+
+#[test]
+fn import_reads_back_exported_notes() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "planning, review")
+        .unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+    let exported = env.sessions();
+    let file = env::temp_dir().join(format!("track-ical-round-trip-{}.ics", process::id()));
+    commands::export(
+        &env.ctx(),
+        Bound::None,
+        Bound::None,
+        None,
+        Some(file.clone()),
+    )
+    .unwrap();
+    let calendar = fs::read_to_string(&file).unwrap();
+    fs::remove_file(file).unwrap();
+
+    let env = Env::with_activity();
+    import(&env, "round-trip", &calendar).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            exported[0].clone(),
+            session(at(11, "09:00"), at(11, "10:00"), "work"),
+        ]
+    );
+}
+
+#[test]
+fn import_skips_events_that_cannot_be_sessions() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    import(
+        &env,
+        "skipped",
+        "BEGIN:VCALENDAR
+BEGIN:VEVENT
+DTSTART;VALUE=DATE:20240611
+SUMMARY:Holiday
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20240610T093000
+DTEND:20240610T103000
+SUMMARY:Clash
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20240621T090000
+DTEND:20240621T100000
+SUMMARY:Tomorrow
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20240612T090000
+SUMMARY:Open
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20240613T090000
+DURATION:P99999999999999W
+SUMMARY:Forever
+END:VEVENT
+BEGIN:VEVENT
+DTSTART:20240613T090000
+DURATION:P1000000DT99999999999999999H
+SUMMARY:Overflow
+END:VEVENT
+END:VCALENDAR
+",
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(10, "09:00"), at(10, "10:00"), "")]
+    );
+
+    let e = import(&env, "invalid", "BEGIN:VEVENT\nEND:VEVENT\n").unwrap_err();
+    assert_eq!(e.to_string(), "error: The file is not an iCalendar file");
+}
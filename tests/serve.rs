@@ -0,0 +1,199 @@
+mod common;
+
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    process, thread,
+};
+
+use common::{at, Env};
+use serde_json::{json, Value};
+use track::{
+    serve::{handle, Server},
+    storage::{Lock, Storage},
+};
+
+impl Env {
+    fn request(&self, method: &str, url: &str, body: &str) -> (u16, Value) {
+        handle(&self.storage, &self.clock, method, url, body)
+    }
+}
+
+#[test]
+fn activities_can_be_created_changed_and_deleted() {
+    let env = Env::new();
+    let (status, created) = env.request("POST", "/activities", r#"{"name": " deep work "}"#);
+    assert_eq!(status, 201);
+    assert_eq!(
+        created,
+        json!({ "name": "deep work", "active": true, "archived": false, "ongoing": null })
+    );
+    let (status, changed) = env.request(
+        "PATCH",
+        "/activities/deep%20work",
+        r#"{"name": "focus", "archived": true}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(
+        changed,
+        json!({ "name": "focus", "active": false, "archived": true, "ongoing": null })
+    );
+    let (status, _) = env.request("DELETE", "/activities/focus", "");
+    assert_eq!(status, 200);
+    assert_eq!(env.request("GET", "/activities", ""), (200, json!([])));
+}
+
+#[test]
+fn sessions_are_started_switched_and_ended() {
+    let env = Env::with_activity();
+    env.request("POST", "/activities", r#"{"name": "play"}"#);
+    env.request("PUT", "/active", r#"{"name": "work"}"#);
+    let (status, started) = env.request("POST", "/start", "");
+    assert_eq!(status, 200);
+    assert_eq!(started["activity"], "work");
+    assert_eq!(started["start"], at(20, "12:00").to_rfc3339());
+
+    env.clock.advance(chrono::Duration::hours(1));
+    let (_, switched) = env.request("POST", "/switch", r#"{"name": "play", "notes": "done"}"#);
+    assert_eq!(switched["activity"], "play");
+    assert_eq!(switched["ongoing"], true);
+
+    env.clock.advance(chrono::Duration::hours(1));
+    env.request("POST", "/end", "");
+    let (_, sessions) = env.request("GET", "/sessions?activity=work", "");
+    assert_eq!(
+        sessions["sessions"][0],
+        json!({
+            "position": 1,
+            "id": sessions["sessions"][0]["id"],
+            "start": at(20, "12:00").to_rfc3339(),
+            "end": at(20, "13:00").to_rfc3339(),
+            "notes": "done",
+        })
+    );
+    let (_, stats) = env.request("GET", "/stats", "");
+    assert_eq!(
+        (&stats["activity"], &stats["total"]),
+        (&json!("play"), &json!(3600))
+    );
+}
+
+#[test]
+fn sessions_and_stats_in_range() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("11/06/24-09:00", "11/06/24-11:00", "").unwrap();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "").unwrap();
+    let from = at(11, "00:00").to_rfc3339().replace('+', "%2B");
+    let to = at(11, "10:00").to_rfc3339().replace('+', "%2B");
+    let query = format!("from={from}&to={to}");
+    let (_, sessions) = env.request("GET", &format!("/sessions?{query}"), "");
+    let sessions = sessions["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["position"], 2);
+    let (_, stats) = env.request("GET", &format!("/stats?{query}"), "");
+    assert_eq!(
+        (&stats["sessions"], &stats["total"]),
+        (&json!(1), &json!(3600))
+    );
+
+    let (status, error) = env.request("GET", "/stats?from=yesterday", "");
+    assert_eq!(status, 400);
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("\"from\" must be"));
+}
+
+#[test]
+fn errors_are_returned_as_json() {
+    let env = Env::with_activity();
+    assert_eq!(
+        env.request("POST", "/end", ""),
+        (
+            400,
+            json!({ "error": "There is no ongoing session of \"work\"" })
+        )
+    );
+    assert_eq!(env.request("POST", "/activities", "{").0, 400);
+    assert_eq!(env.request("POST", "/activities", "{}").0, 400);
+    assert_eq!(env.request("GET", "/sessions/all", "").0, 404);
+}
+
+#[test]
+fn unknown_activities_are_not_found_and_broken_data_is_a_server_error() {
+    let env = Env::with_activity();
+    assert_eq!(
+        env.request("GET", "/activities/play", ""),
+        (
+            404,
+            json!({ "error": "No activity with the name \"play\" exists" })
+        )
+    );
+    assert_eq!(env.request("PATCH", "/activities/play", "{}").0, 404);
+    assert_eq!(env.request("DELETE", "/activities/play", "").0, 404);
+    assert_eq!(env.request("PUT", "/active", r#"{"name": "play"}"#).0, 404);
+    assert_eq!(env.request("POST", "/switch", r#"{"name": "play"}"#).0, 404);
+    assert_eq!(env.request("GET", "/sessions?activity=play", "").0, 404);
+
+    env.storage.write("0", b"broken").unwrap();
+    assert_eq!(env.request("GET", "/activities", "").0, 500);
+    assert_eq!(env.request("POST", "/start", "").0, 500);
+}
+
+#[test]
+fn plus_is_a_space_only_in_queries() {
+    let env = Env::new();
+    env.request("POST", "/activities", r#"{"name": "deep work+"}"#);
+    env.add("20/06/24-09:00", "20/06/24-10:00", "").unwrap();
+    let (status, sessions) = env.request("GET", "/sessions?activity=deep+work%2B", "");
+    assert_eq!(status, 200);
+    assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+    let (_, activity) = env.request("GET", "/activities/deep%20work+", "");
+    assert_eq!(activity["name"], "deep work+");
+    assert_eq!(env.request("GET", "/activities/deep%+1", "").0, 400);
+    assert_eq!(env.request("GET", "/activities/deep%-1", "").0, 400);
+}
+
+/// Sends a request to the server at `addr`, returning the response
+fn send(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn server_checks_token_and_locks_changes() {
+    let dir = env::temp_dir().join(format!("track-serve-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let server = Server::bind("127.0.0.1:0", dir.clone(), Some("secret".to_string())).unwrap();
+    let addr = server.addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let request = |method: &str, path: &str, token: &str| {
+        send(
+            addr,
+            &format!(
+                "{method} {path} HTTP/1.1\r\nHost: track\r\nAuthorization: Bearer {token}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            ),
+        )
+    };
+    let status = |token: &str| request("GET", "/status", token);
+    for wrong in ["wrong", "secre", "secrets", ""] {
+        assert!(status(wrong).starts_with("HTTP/1.1 401"));
+    }
+    let response = status("secret");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with(r#""ongoing":false,"start":null,"today":null}"#));
+    let lock = Lock::acquire(&dir).unwrap();
+    assert!(status("secret").starts_with("HTTP/1.1 200"));
+    assert!(request("POST", "/start", "secret").starts_with("HTTP/1.1 503"));
+    drop(lock);
+    let response = request("POST", "/start", "secret");
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(response.ends_with(r#"{"error":"No activity currently active"}"#));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,539 @@
+mod common;
+
+use chrono::Duration;
+use common::{abs, at, positions, session, Env};
+use track::{commands, Bound, Filter, Position};
+
+#[test]
+fn add_keeps_sessions_in_order() {
+    let env = Env::with_activity();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "second")
+        .unwrap();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "first")
+        .unwrap();
+    env.add("14/06/24-09:00", "14/06/24-10:00", "third")
+        .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(10, "09:00"), at(10, "10:00"), "first"),
+            session(at(12, "09:00"), at(12, "10:00"), "second"),
+            session(at(14, "09:00"), at(14, "10:00"), "third"),
+        ]
+    );
+}
+
+#[test]
+fn add_time_is_on_the_date_of_the_clock() {
+    let env = Env::with_activity();
+    env.add("09:00", "10:30", "").unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(20, "09:00"), at(20, "10:30"), "")]
+    );
+}
+
+#[test]
+fn add_rejects_overlapping_session() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-11:00", "").unwrap();
+    let e = env.add("10/06/24-10:00", "10/06/24-12:00", "").unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("error: Session overlaps existing session"));
+    assert!(env.add("10/06/24-08:00", "10/06/24-09:30", "").is_err());
+    assert!(env.add("10/06/24-09:15", "10/06/24-09:45", "").is_err());
+    assert_eq!(env.sessions().len(), 1);
+}
+
+#[test]
+fn add_allows_touching_sessions() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("10/06/24-10:00", "10/06/24-11:00", "").unwrap();
+    env.add("10/06/24-08:00", "10/06/24-09:00", "").unwrap();
+    assert_eq!(env.sessions().len(), 3);
+}
+
+#[test]
+fn add_rejects_invalid_times() {
+    let env = Env::with_activity();
+    let e = env.add("10/06/24-10:00", "10/06/24-09:00", "").unwrap_err();
+    assert_eq!(e.to_string(), "error: Session must end after it starts");
+    let e = env.add("20/06/24-11:00", "20/06/24-13:00", "").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "error: Session cannot have ended in the future"
+    );
+    env.clock.advance(Duration::hours(1));
+    env.add("20/06/24-11:00", "20/06/24-13:00", "").unwrap();
+}
+
+#[test]
+fn no_overlap_setting_rejects_overlap_with_other_activities() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-11:00", "").unwrap();
+    commands::create(&env.ctx(), "play".to_string()).unwrap();
+    env.add("10/06/24-10:00", "10/06/24-12:00", "").unwrap();
+    env.add("11/06/24-10:00", "11/06/24-12:00", "").unwrap();
+
+    commands::config(
+        &env.ctx(),
+        Some("no-overlap".to_string()),
+        Some("true".to_string()),
+        false,
+    )
+    .unwrap();
+    let e = env.add("10/06/24-08:00", "10/06/24-09:30", "").unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("error: Session overlaps existing session of \"work\""));
+    env.add("10/06/24-07:00", "10/06/24-08:00", "").unwrap();
+    assert_eq!(env.sessions().len(), 3);
+}
+
+#[test]
+fn no_overlap_setting_checks_sessions_moved_and_copied() {
+    let env = Env::with_activity();
+    commands::create(&env.ctx(), "play".to_string()).unwrap();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    commands::create(&env.ctx(), "rest".to_string()).unwrap();
+    env.add("10/06/24-09:30", "10/06/24-10:30", "").unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+    commands::config(
+        &env.ctx(),
+        Some("no-overlap".to_string()),
+        Some("true".to_string()),
+        false,
+    )
+    .unwrap();
+
+    commands::copy(&env.ctx(), vec![Position::Index(2)], "work".to_string()).unwrap();
+    let e = commands::move_sessions(&env.ctx(), vec![Position::Index(1)], "work".to_string())
+        .unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("error: Session overlaps existing session of \"play\""));
+    assert_eq!(env.sessions().len(), 2);
+    commands::set(&env.ctx(), "work".to_string()).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(11, "09:00"), at(11, "10:00"), "")]
+    );
+}
+
+#[test]
+fn start_and_end_use_the_clock() {
+    let env = Env::with_activity();
+    commands::start(&env.ctx()).unwrap();
+    assert_eq!(env.current().ongoing, Some(at(20, "12:00")));
+    env.clock.advance(Duration::minutes(90));
+    commands::end(&env.ctx(), "done".to_string()).unwrap();
+    assert_eq!(env.current().ongoing, None);
+    assert_eq!(
+        env.sessions(),
+        [session(at(20, "12:00"), at(20, "13:30"), "done")]
+    );
+}
+
+#[test]
+fn past_ends_at_the_current_time() {
+    let env = Env::with_activity();
+    commands::past(&env.ctx(), 0, 1, 2, 30, String::new()).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(19, "09:30"), at(20, "12:00"), "")]
+    );
+}
+
+#[test]
+fn edit_changes_start_end_and_notes() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "old").unwrap();
+    let ctx = env.ctx();
+    commands::edit(
+        &ctx,
+        positions(&[1]),
+        Some(abs("10/06/24-08:30")),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    commands::edit(&ctx, positions(&[1]), None, Some(abs("11:00")), None, None).unwrap();
+    commands::edit(
+        &ctx,
+        positions(&[1]),
+        None,
+        None,
+        Some("new".to_string()),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(10, "08:30"), at(10, "11:00"), "new")]
+    );
+}
+
+#[test]
+fn edit_moves_session_into_order() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "first")
+        .unwrap();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "second")
+        .unwrap();
+    commands::edit(
+        &env.ctx(),
+        positions(&[1]),
+        Some(abs("14/06/24-09:00")),
+        Some(abs("10:00")),
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(12, "09:00"), at(12, "10:00"), "second"),
+            session(at(14, "09:00"), at(14, "10:00"), "first"),
+        ]
+    );
+}
+
+#[test]
+fn edit_rejects_overlap_and_leaves_sessions_unchanged() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("10/06/24-11:00", "10/06/24-12:00", "").unwrap();
+    let before = env.sessions();
+    let e = commands::edit(
+        &env.ctx(),
+        positions(&[1]),
+        None,
+        Some(abs("11:30")),
+        None,
+        None,
+    )
+    .unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("error: Session overlaps existing session"));
+    assert_eq!(env.sessions(), before);
+}
+
+#[test]
+fn edit_shifts_several_sessions_after_confirmation() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("10/06/24-10:00", "10/06/24-11:00", "").unwrap();
+    let filter = Filter {
+        positions: vec![Position::Range(1, 2)],
+        from: Bound::None,
+        to: Bound::None,
+        notes: None,
+    };
+    let shift = Some(Duration::minutes(-30));
+    commands::edit(&env.ctx_with("n\n"), filter, None, None, None, shift).unwrap();
+    assert_eq!(env.sessions()[0].0, at(10, "09:00"));
+
+    let filter = Filter {
+        positions: vec![Position::Range(1, 2)],
+        from: Bound::None,
+        to: Bound::None,
+        notes: None,
+    };
+    commands::edit(&env.ctx_with("y\n"), filter, None, None, None, shift).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(10, "08:30"), at(10, "09:30"), ""),
+            session(at(10, "09:30"), at(10, "10:30"), ""),
+        ]
+    );
+}
+
+#[test]
+fn edit_rejects_shifts_too_long_to_hold() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    let shift = track::parse_duration("100000000w").unwrap();
+    let e = commands::edit(&env.ctx(), positions(&[1]), None, None, None, Some(shift)).unwrap_err();
+    assert_eq!(e.to_string(), "error: Sessions cannot be shifted that far");
+    assert_eq!(
+        env.sessions(),
+        [session(at(10, "09:00"), at(10, "10:00"), "")]
+    );
+    assert!(track::parse_duration("9999999999999999w").is_none());
+    assert!(track::parse_duration("99999999999999w").is_none());
+    let e = commands::config(
+        &env.ctx(),
+        Some("max-session".to_string()),
+        Some("99999999999999w".to_string()),
+        false,
+    )
+    .unwrap_err();
+    assert!(e.to_string().starts_with("error: \"max-session\" must be"));
+}
+
+#[test]
+fn remove_requires_confirmation() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    commands::remove(&env.ctx(), positions(&[1])).unwrap();
+    assert_eq!(env.sessions().len(), 1);
+    commands::remove(&env.ctx_with("y\n"), positions(&[1])).unwrap();
+    assert!(env.sessions().is_empty());
+}
+
+#[test]
+fn remove_last_and_missing_positions() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "first")
+        .unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "second")
+        .unwrap();
+    let e = commands::remove(&env.ctx_with("y\n"), positions(&[3])).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "error: No session of the active activity with this index exists"
+    );
+    let filter = Filter {
+        positions: vec![Position::Last],
+        from: Bound::None,
+        to: Bound::None,
+        notes: None,
+    };
+    commands::remove(&env.ctx_with("y\n"), filter).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(10, "09:00"), at(10, "10:00"), "first")]
+    );
+}
+
+#[test]
+fn remove_sessions_in_range() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+    env.add("11/06/24-23:00", "12/06/24-01:00", "").unwrap();
+    env.add("13/06/24-09:00", "13/06/24-10:00", "").unwrap();
+    let filter = Filter {
+        positions: Vec::new(),
+        from: Bound::Absolute(abs("11/06/24")),
+        to: Bound::Absolute(abs("11/06/24")),
+        notes: None,
+    };
+    commands::remove(&env.ctx_with("y\n"), filter).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(10, "09:00"), at(10, "10:00"), ""),
+            session(at(13, "09:00"), at(13, "10:00"), ""),
+        ]
+    );
+}
+
+#[test]
+fn remove_sessions_matching_notes_in_the_past() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "meeting")
+        .unwrap();
+    env.add("18/06/24-09:00", "18/06/24-10:00", "meeting")
+        .unwrap();
+    env.add("19/06/24-09:00", "19/06/24-10:00", "coding")
+        .unwrap();
+    let filter = Filter {
+        positions: Vec::new(),
+        from: Bound::Ago {
+            weeks: 0,
+            days: 7,
+            hours: 0,
+            minutes: 0,
+        },
+        to: Bound::Now,
+        notes: Some("meeting".to_string()),
+    };
+    commands::remove(&env.ctx_with("y\n"), filter).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(10, "09:00"), at(10, "10:00"), "meeting"),
+            session(at(19, "09:00"), at(19, "10:00"), "coding"),
+        ]
+    );
+}
+
+#[test]
+fn split_halves_touch_and_can_be_edited_and_joined() {
+    let env = Env::with_activity();
+    env.add("20/06/24-10:00", "20/06/24-11:00", "both").unwrap();
+    commands::split_session(
+        &env.ctx(),
+        Position::Index(1),
+        abs("10:30"),
+        Some("first".to_string()),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(20, "10:00"), at(20, "10:30"), "first"),
+            session(at(20, "10:30"), at(20, "11:00"), "both"),
+        ]
+    );
+    let notes = Some("second".to_string());
+    commands::edit(&env.ctx(), positions(&[2]), None, None, notes, None).unwrap();
+    let start = Some(abs("09:45"));
+    commands::edit(&env.ctx(), positions(&[1]), start, None, None, None).unwrap();
+    commands::join(&env.ctx(), Position::Index(1), Position::Index(2), None).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(20, "09:45"), at(20, "11:00"), "first; second")]
+    );
+}
+
+#[test]
+fn split_session_ignores_overlap_it_does_not_add() {
+    let env = Env::with_activity();
+    env.add("20/06/24-10:00", "20/06/24-11:00", "").unwrap();
+    commands::create(&env.ctx(), "play".to_string()).unwrap();
+    env.add("20/06/24-10:30", "20/06/24-11:30", "").unwrap();
+    commands::config(
+        &env.ctx(),
+        Some("no-overlap".to_string()),
+        Some("true".to_string()),
+        false,
+    )
+    .unwrap();
+    commands::set(&env.ctx(), "work".to_string()).unwrap();
+    commands::split_session(&env.ctx(), Position::Index(1), abs("10:15"), None, None).unwrap();
+    assert_eq!(env.sessions().len(), 2);
+}
+
+#[test]
+fn join_asks_before_including_a_long_gap() {
+    let env = Env::with_activity();
+    env.add("19/06/24-09:00", "19/06/24-10:00", "").unwrap();
+    env.add("19/06/24-10:30", "19/06/24-11:00", "").unwrap();
+    env.add("19/06/24-14:00", "19/06/24-15:00", "").unwrap();
+    commands::join(&env.ctx(), Position::Index(1), Position::Index(2), None).unwrap();
+    commands::join(
+        &env.ctx_with("n\n"),
+        Position::Index(1),
+        Position::Index(2),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(19, "09:00"), at(19, "11:00"), ""),
+            session(at(19, "14:00"), at(19, "15:00"), ""),
+        ]
+    );
+    commands::join(
+        &env.ctx_with("y\n"),
+        Position::Index(1),
+        Position::Index(2),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(19, "09:00"), at(19, "15:00"), "")]
+    );
+}
+
+#[test]
+fn gaps_between_sessions_are_filled() {
+    let env = Env::with_activity();
+    env.add("20/06/24-09:00", "20/06/24-10:00", "").unwrap();
+    env.add("20/06/24-11:00", "20/06/24-12:00", "").unwrap();
+    commands::gaps(
+        &env.ctx_with("work\nreview\n"),
+        Bound::None,
+        Bound::None,
+        1,
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(20, "09:00"), at(20, "10:00"), ""),
+            session(at(20, "10:00"), at(20, "11:00"), "review"),
+            session(at(20, "11:00"), at(20, "12:00"), ""),
+        ]
+    );
+}
+
+fn ids(ids: &[&str]) -> Filter {
+    Filter {
+        positions: ids.iter().map(|id| Position::Id(id.to_string())).collect(),
+        from: Bound::None,
+        to: Bound::None,
+        notes: None,
+    }
+}
+
+#[test]
+fn ids_follow_sessions_that_move() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "first")
+        .unwrap();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "second")
+        .unwrap();
+    let id = env.current().sessions[0].short_id();
+    let notes = |notes: &str| Some(notes.to_string());
+    commands::edit(
+        &env.ctx(),
+        ids(&[&id]),
+        Some(abs("14/06/24-09:00")),
+        Some(abs("10:00")),
+        None,
+        None,
+    )
+    .unwrap();
+    commands::edit(&env.ctx(), ids(&[&id]), None, None, notes("moved"), None).unwrap();
+    assert_eq!(env.current().sessions[1].short_id(), id);
+    assert_eq!(
+        env.sessions(),
+        [
+            session(at(12, "09:00"), at(12, "10:00"), "second"),
+            session(at(14, "09:00"), at(14, "10:00"), "moved"),
+        ]
+    );
+}
+
+#[test]
+fn ids_must_match_exactly_one_session() {
+    let env = Env::with_activity();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+    let mut current = env.current();
+    current.sessions[0].id = 0xabc1 << 48;
+    current.sessions[1].id = 0xabc2 << 48;
+    current.write(&env.storage, 0).unwrap();
+
+    let e = commands::remove(&env.ctx_with("y\n"), ids(&["abc"])).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "error: More than one session of the active activity has an id beginning with \"abc\""
+    );
+    let e = commands::remove(&env.ctx_with("y\n"), ids(&["abd"])).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "error: No session of the active activity with this id exists"
+    );
+    commands::remove(&env.ctx_with("y\n"), ids(&["abc2"])).unwrap();
+    assert_eq!(
+        env.sessions(),
+        [session(at(10, "09:00"), at(10, "10:00"), "")]
+    );
+    assert_eq!(
+        commands::positions(&env.ctx()).last().unwrap().0,
+        "@abc10000"
+    );
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use chrono::Duration;
+use common::{abs, at, Env};
+use track::{
+    data::{Activity, Data},
+    stats::{convert_bounds, convert_bounds_all},
+    Bound,
+};
+
+fn activity(sessions: &[(&str, &str)]) -> Activity {
+    let env = Env::with_activity();
+    for (start, end) in sessions {
+        env.add(start, end, "").unwrap();
+    }
+    env.current()
+}
+
+#[test]
+fn bounds_default_to_the_recorded_sessions() {
+    let activity = activity(&[
+        ("10/06/24-09:00", "10/06/24-10:00"),
+        ("12/06/24-09:00", "12/06/24-11:00"),
+    ]);
+    let now = at(20, "12:00");
+    assert_eq!(
+        activity
+            .convert_bounds(Bound::None, Bound::None, now)
+            .unwrap(),
+        (at(10, "09:00"), at(12, "11:00"))
+    );
+    assert_eq!(
+        activity
+            .convert_bounds(Bound::Absolute(abs("11/06/24")), Bound::Now, now)
+            .unwrap(),
+        (at(11, "00:00"), now)
+    );
+}
+
+#[test]
+fn bounds_resolve_dates_times_and_durations() {
+    let (first, last, now) = (at(1, "00:00"), at(2, "00:00"), at(20, "12:00"));
+    let on = Bound::Absolute(abs("15/06/24"));
+    assert_eq!(
+        convert_bounds(first, last, on, on, now).unwrap(),
+        (at(15, "00:00"), at(16, "00:00"))
+    );
+    let range = (
+        Bound::Absolute(abs("15/06/24-09:00")),
+        Bound::Absolute(abs("17:00")),
+    );
+    assert_eq!(
+        convert_bounds(first, last, range.0, range.1, now).unwrap(),
+        (at(15, "09:00"), at(15, "17:00"))
+    );
+    let ago = Bound::Ago {
+        weeks: 1,
+        days: 1,
+        hours: 1,
+        minutes: 1,
+    };
+    assert_eq!(
+        convert_bounds(first, last, ago, Bound::Now, now).unwrap(),
+        (at(12, "10:59"), now)
+    );
+    let since = Bound::Absolute(abs("08:00"));
+    assert_eq!(
+        convert_bounds(first, last, since, Bound::Now, now).unwrap(),
+        (at(20, "08:00"), now)
+    );
+}
+
+#[test]
+fn bounds_must_be_in_order() {
+    let (first, last, now) = (at(1, "00:00"), at(2, "00:00"), at(20, "12:00"));
+    let e = convert_bounds(
+        first,
+        last,
+        Bound::Absolute(abs("16/06/24")),
+        Bound::Absolute(abs("15/06/24-12:00")),
+        now,
+    )
+    .unwrap_err();
+    assert_eq!(e.to_string(), "error: Start of range must be before end");
+    assert!(Activity::new()
+        .convert_bounds(Bound::None, Bound::None, now)
+        .is_err());
+}
+
+#[test]
+fn sessions_in_range_include_partial_overlaps() {
+    let activity = activity(&[
+        ("10/06/24-09:00", "10/06/24-10:00"),
+        ("10/06/24-23:00", "11/06/24-01:00"),
+        ("11/06/24-09:00", "11/06/24-10:00"),
+        ("12/06/24-09:00", "12/06/24-10:00"),
+    ]);
+    assert_eq!(
+        activity.get_in_range(at(11, "00:00"), at(12, "00:00")),
+        (1, 3)
+    );
+    assert_eq!(
+        activity.get_in_range(at(10, "10:00"), at(10, "23:00")),
+        (1, 1)
+    );
+    assert_eq!(
+        activity.get_in_range(at(1, "00:00"), at(30, "00:00")),
+        (0, 4)
+    );
+    assert_eq!(
+        activity.time_in_range(at(11, "00:00"), at(12, "00:00")),
+        Duration::hours(2)
+    );
+    assert_eq!(
+        activity.time_in_range(at(10, "09:30"), at(12, "09:15")),
+        Duration::minutes(30 + 120 + 60 + 15)
+    );
+}
+
+#[test]
+fn summary_of_range() {
+    let activity = activity(&[
+        ("10/06/24-09:00", "10/06/24-10:00"),
+        ("11/06/24-09:00", "11/06/24-12:00"),
+    ]);
+    let summary = activity.summary(at(10, "00:00"), at(12, "00:00")).unwrap();
+    assert_eq!(summary.sessions, 2);
+    assert_eq!(summary.total, Duration::hours(4));
+    assert_eq!(summary.per_day, Duration::hours(2));
+    assert_eq!(summary.average, Duration::hours(2));
+    assert!((summary.proportion - 4. / 48.).abs() < 1e-9);
+    assert!(activity.summary(at(12, "00:00"), at(13, "00:00")).is_none());
+}
+
+#[test]
+fn bounds_across_activities() {
+    let env = Env::with_activity();
+    env.add("12/06/24-09:00", "12/06/24-10:00", "").unwrap();
+    track::commands::create(&env.ctx(), "play".to_string()).unwrap();
+    env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+    env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+    let data = Data::read(&env.storage).unwrap();
+    let activities: Vec<_> = data
+        .activities
+        .iter()
+        .map(|info| Activity::read(&env.storage, info.id).unwrap())
+        .collect();
+    assert_eq!(
+        convert_bounds_all(activities.iter(), Bound::None, Bound::None, at(20, "12:00")).unwrap(),
+        (at(10, "09:00"), at(12, "10:00"))
+    );
+}
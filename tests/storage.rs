@@ -0,0 +1,232 @@
+mod common;
+
+use common::{at, session};
+use track::{
+    data::{Activity, Session},
+    storage::{self, MemoryStorage, Storage},
+};
+
+fn activity(sessions: &[(u32, &str, &str, &str)]) -> Activity {
+    Activity {
+        ongoing: Some(at(20, "11:00")),
+        sessions: sessions
+            .iter()
+            .map(|&(day, start, end, notes)| {
+                Session::new(at(day, start), at(day, end), notes.to_string())
+            })
+            .collect(),
+        notes: "ongoing".to_string(),
+    }
+}
+
+fn sessions(activity: &Activity) -> Vec<(track::DateTime, track::DateTime, String)> {
+    activity
+        .sessions
+        .iter()
+        .map(|s| session(s.start, s.end, &s.notes))
+        .collect()
+}
+
+fn check_activities(storage: &dyn Storage) {
+    assert!(storage.read_activity(0).unwrap().is_none());
+    let first = activity(&[
+        (10, "09:00", "10:00", "a"),
+        (11, "09:00", "10:00", "a"),
+        (12, "09:00", "10:00", ""),
+    ]);
+    storage.write_activity(0, &first).unwrap();
+    storage.write_activity(3, &Activity::new()).unwrap();
+    assert_eq!(storage.activity_ids().unwrap(), [0, 3]);
+
+    let read = storage.read_activity(0).unwrap().unwrap();
+    assert_eq!(read.ongoing, first.ongoing);
+    assert_eq!(read.notes, first.notes);
+    assert_eq!(sessions(&read), sessions(&first));
+
+    let second = activity(&[
+        (9, "09:00", "10:00", "new"),
+        (11, "09:00", "10:00", "a"),
+        (12, "09:00", "10:00", "edited"),
+    ]);
+    storage.write_activity(0, &second).unwrap();
+    let read = storage.read_activity(0).unwrap().unwrap();
+    assert_eq!(sessions(&read), sessions(&second));
+
+    storage.remove_activity(0).unwrap();
+    assert!(storage.read_activity(0).unwrap().is_none());
+    assert_eq!(storage.activity_ids().unwrap(), [3]);
+}
+
+fn check_ranges(storage: &dyn Storage) {
+    storage
+        .write_activity(
+            0,
+            &activity(&[
+                (10, "09:00", "10:00", ""),
+                (10, "23:00", "23:59", ""),
+                (11, "00:00", "01:00", ""),
+                (12, "09:00", "10:00", ""),
+            ]),
+        )
+        .unwrap();
+    storage.write_activity(1, &Activity::new()).unwrap();
+    assert_eq!(
+        storage.recorded_range(0).unwrap(),
+        Some((at(10, "09:00"), at(12, "10:00")))
+    );
+    assert_eq!(storage.recorded_range(1).unwrap(), None);
+
+    let (offset, sessions) = storage
+        .sessions_in_range(0, at(10, "23:30"), at(12, "00:00"))
+        .unwrap();
+    assert_eq!(offset, 1);
+    assert_eq!(
+        sessions.iter().map(|s| s.start).collect::<Vec<_>>(),
+        [at(10, "23:00"), at(11, "00:00")]
+    );
+    let (offset, sessions) = storage
+        .sessions_in_range(0, at(13, "00:00"), at(14, "00:00"))
+        .unwrap();
+    assert_eq!((offset, sessions.len()), (4, 0));
+}
+
+/// Checks the totals of the storage against those of the activity, over ranges that cover whole
+/// and partial days
+fn check_totals_match(storage: &dyn Storage, activity: &Activity) {
+    let times: Vec<_> = (8..=22)
+        .flat_map(|day| [at(day, "00:00"), at(day, "09:30"), at(day, "23:45")])
+        .collect();
+    for (k, &from) in times.iter().enumerate() {
+        for &to in &times[k + 1..] {
+            let (i, j) = activity.get_in_range(from, to);
+            assert_eq!(
+                storage.time_in_range(0, from, to).unwrap(),
+                (j - i, activity.time_in_range(from, to))
+            );
+        }
+    }
+}
+
+fn check_totals(storage: &dyn Storage) {
+    let mut first = activity(&[
+        (10, "09:00", "10:00", ""),
+        (10, "22:00", "23:59", ""),
+        (12, "09:00", "17:00", ""),
+        (15, "09:00", "10:00", ""),
+    ]);
+    first.sessions.push(Session::new(
+        at(15, "20:00"),
+        at(18, "02:00"),
+        String::new(),
+    ));
+    storage.write_activity(0, &first).unwrap();
+    check_totals_match(storage, &first);
+
+    let second = activity(&[
+        (10, "22:00", "23:59", ""),
+        (12, "09:00", "17:00", "edited"),
+        (14, "09:00", "12:00", ""),
+    ]);
+    storage.write_activity(0, &second).unwrap();
+    check_totals_match(storage, &second);
+}
+
+#[test]
+fn files_store_activities() {
+    check_activities(&MemoryStorage::default());
+}
+
+#[test]
+fn files_query_ranges() {
+    check_ranges(&MemoryStorage::default());
+}
+
+#[test]
+fn files_total_time() {
+    check_totals(&MemoryStorage::default());
+}
+
+#[test]
+fn copy_moves_files_and_activities() {
+    let from = MemoryStorage::default();
+    from.write("data", b"index").unwrap();
+    from.write("config", b"settings").unwrap();
+    from.write_activity(2, &activity(&[(10, "09:00", "10:00", "")]))
+        .unwrap();
+    let to = MemoryStorage::default();
+    assert_eq!(storage::copy(&from, &to).unwrap(), 1);
+    assert_eq!(to.read("data").unwrap().unwrap(), b"index");
+    assert_eq!(to.read("config").unwrap().unwrap(), b"settings");
+    assert!(to.read("idle").unwrap().is_none());
+    assert_eq!(to.read_activity(2).unwrap().unwrap().sessions.len(), 1);
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use common::Env;
+    use track::{
+        clock::FixedClock,
+        commands::{self, Context},
+        data::Data,
+        storage::SqliteStorage,
+    };
+
+    fn open() -> SqliteStorage {
+        SqliteStorage::open(":memory:").unwrap()
+    }
+
+    #[test]
+    fn sqlite_stores_activities() {
+        check_activities(&open());
+    }
+
+    #[test]
+    fn sqlite_queries_ranges() {
+        check_ranges(&open());
+    }
+
+    #[test]
+    fn sqlite_total_time() {
+        check_totals(&open());
+    }
+
+    #[test]
+    fn sqlite_files() {
+        let storage = open();
+        assert!(storage.read("data").unwrap().is_none());
+        storage.write("data", b"first").unwrap();
+        storage.write("data", b"second").unwrap();
+        assert_eq!(storage.read("data").unwrap().unwrap(), b"second");
+        assert_eq!(storage.names().unwrap(), ["data"]);
+        storage.remove("data").unwrap();
+        assert!(storage.names().unwrap().is_empty());
+    }
+
+    #[test]
+    fn commands_on_migrated_data() {
+        let env = Env::with_activity();
+        env.add("10/06/24-09:00", "10/06/24-10:00", "").unwrap();
+        env.add("11/06/24-09:00", "11/06/24-10:00", "").unwrap();
+        let sqlite = open();
+        assert_eq!(storage::copy(&env.storage, &sqlite).unwrap(), 1);
+
+        let clock = FixedClock::new(at(20, "12:00"));
+        let ctx = Context::new(&sqlite, &clock, "y\n".as_bytes());
+        commands::add(
+            &ctx,
+            common::abs("12/06/24-09:00"),
+            common::abs("10:00"),
+            String::new(),
+        )
+        .unwrap();
+        commands::remove(&ctx, common::positions(&[1])).unwrap();
+        let data = Data::read(&sqlite).unwrap();
+        let (current, name) = data.read_current(&sqlite).unwrap();
+        assert_eq!(name, "work");
+        assert_eq!(
+            current.sessions.iter().map(|s| s.start).collect::<Vec<_>>(),
+            [at(11, "09:00"), at(12, "09:00")]
+        );
+    }
+}
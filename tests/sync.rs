@@ -0,0 +1,250 @@
+mod common;
+
+use common::{abs, at, positions, session, Env};
+use track::{
+    commands,
+    data::Activity,
+    storage::{MemoryStorage, Storage},
+    sync::sync,
+};
+
+/// Two machines that sync through a shared folder
+struct Machines {
+    laptop: Env,
+    desktop: Env,
+    shared: MemoryStorage,
+}
+
+impl Machines {
+    /// Machines that both have the sessions of "work" added on the laptop
+    fn new() -> Self {
+        let machines = Self {
+            laptop: Env::with_activity(),
+            desktop: Env::new(),
+            shared: MemoryStorage::default(),
+        };
+        machines
+            .laptop
+            .add("10/06/24-09:00", "10/06/24-10:00", "first")
+            .unwrap();
+        machines
+            .laptop
+            .add("11/06/24-09:00", "11/06/24-10:00", "second")
+            .unwrap();
+        machines.sync_laptop();
+        machines.sync_desktop();
+        commands::set(&machines.desktop.ctx(), "work".to_string()).unwrap();
+        machines
+    }
+
+    fn sync_laptop(&self) -> track::sync::Report {
+        sync(&self.laptop.storage, &self.shared).unwrap()
+    }
+
+    fn sync_desktop(&self) -> track::sync::Report {
+        sync(&self.desktop.storage, &self.shared).unwrap()
+    }
+}
+
+#[test]
+fn first_sync_copies_everything() {
+    let machines = Machines::new();
+    assert_eq!(machines.desktop.sessions(), machines.laptop.sessions());
+    assert_eq!(machines.desktop.sessions().len(), 2);
+    let report = machines.sync_desktop();
+    assert_eq!((report.received, report.sent), (0, 0));
+}
+
+#[test]
+fn sessions_added_on_both_are_combined() {
+    let machines = Machines::new();
+    machines
+        .laptop
+        .add("12/06/24-09:00", "12/06/24-10:00", "laptop")
+        .unwrap();
+    machines
+        .desktop
+        .add("13/06/24-09:00", "13/06/24-10:00", "desktop")
+        .unwrap();
+    let report = machines.sync_laptop();
+    assert_eq!((report.received, report.sent), (0, 1));
+    let report = machines.sync_desktop();
+    assert_eq!((report.received, report.sent), (1, 1));
+    machines.sync_laptop();
+    assert_eq!(machines.laptop.sessions(), machines.desktop.sessions());
+    assert_eq!(
+        machines
+            .laptop
+            .sessions()
+            .into_iter()
+            .map(|(.., notes)| notes)
+            .collect::<Vec<_>>(),
+        ["first", "second", "laptop", "desktop"]
+    );
+}
+
+#[test]
+fn removals_and_edits_are_synced() {
+    let machines = Machines::new();
+    commands::remove(&machines.laptop.ctx_with("y\n"), positions(&[1])).unwrap();
+    commands::edit(
+        &machines.desktop.ctx(),
+        positions(&[2]),
+        None,
+        Some(abs("11:00")),
+        None,
+        None,
+    )
+    .unwrap();
+    machines.sync_laptop();
+    machines.sync_desktop();
+    machines.sync_laptop();
+    let expected = [session(at(11, "09:00"), at(11, "11:00"), "second")];
+    assert_eq!(machines.laptop.sessions(), expected);
+    assert_eq!(machines.desktop.sessions(), expected);
+}
+
+#[test]
+fn conflicting_edits_are_reported() {
+    let machines = Machines::new();
+    let edit = |env: &Env, notes: &str| {
+        commands::edit(
+            &env.ctx(),
+            positions(&[1]),
+            None,
+            None,
+            Some(notes.to_string()),
+            None,
+        )
+        .unwrap()
+    };
+    edit(&machines.laptop, "laptop");
+    edit(&machines.desktop, "desktop");
+    assert!(machines.sync_laptop().conflicts.is_empty());
+    let report = machines.sync_desktop();
+    assert_eq!(report.conflicts.len(), 1);
+    assert!(report.conflicts[0].contains("was edited on both sides"));
+    machines.sync_laptop();
+    assert_eq!(machines.laptop.sessions()[0].2, "desktop");
+    assert_eq!(machines.desktop.sessions()[0].2, "desktop");
+}
+
+#[test]
+fn edited_session_removed_elsewhere_is_kept() {
+    let machines = Machines::new();
+    commands::remove(&machines.laptop.ctx_with("y\n"), positions(&[1])).unwrap();
+    commands::edit(
+        &machines.desktop.ctx(),
+        positions(&[1]),
+        None,
+        None,
+        Some("edited".to_string()),
+        None,
+    )
+    .unwrap();
+    machines.sync_laptop();
+    let report = machines.sync_desktop();
+    assert_eq!(report.conflicts.len(), 1);
+    assert!(report.conflicts[0].contains("removed in the other data"));
+    machines.sync_laptop();
+    assert_eq!(machines.laptop.sessions()[0].2, "edited");
+}
+
+#[test]
+fn overlapping_sessions_are_reported() {
+    let machines = Machines::new();
+    machines
+        .laptop
+        .add("12/06/24-09:00", "12/06/24-10:00", "")
+        .unwrap();
+    machines
+        .desktop
+        .add("12/06/24-09:30", "12/06/24-10:30", "")
+        .unwrap();
+    machines.sync_laptop();
+    let report = machines.sync_desktop();
+    assert_eq!(report.overlaps.len(), 1);
+    assert_eq!(machines.desktop.sessions().len(), 4);
+}
+
+#[test]
+fn activities_are_matched_by_name() {
+    let machines = Machines::new();
+    commands::create(&machines.desktop.ctx(), "play".to_string()).unwrap();
+    machines
+        .desktop
+        .add("12/06/24-18:00", "12/06/24-19:00", "")
+        .unwrap();
+    commands::delete(&machines.laptop.ctx_with("y\n"), "work".to_string()).unwrap();
+    machines.sync_laptop();
+    machines.sync_desktop();
+    machines.sync_laptop();
+    let names = |env: &Env| commands::activity_names(&env.ctx(), None);
+    assert_eq!(names(&machines.laptop), ["play"]);
+    assert_eq!(names(&machines.desktop), ["play"]);
+    commands::set(&machines.laptop.ctx(), "play".to_string()).unwrap();
+    assert_eq!(machines.laptop.sessions().len(), 1);
+}
+
+#[test]
+fn renamed_activities_keep_their_sessions() {
+    let machines = Machines::new();
+    let rename = |env: &Env, to: &str| {
+        commands::rename(&env.ctx(), "work".to_string(), to.to_string()).unwrap()
+    };
+    rename(&machines.laptop, "job");
+    machines
+        .desktop
+        .add("12/06/24-09:00", "12/06/24-10:00", "desktop")
+        .unwrap();
+    assert!(machines.sync_laptop().conflicts.is_empty());
+    assert!(machines.sync_desktop().conflicts.is_empty());
+    machines.sync_laptop();
+    let names = |env: &Env| commands::activity_names(&env.ctx(), None);
+    assert_eq!(names(&machines.laptop), ["job"]);
+    assert_eq!(names(&machines.desktop), ["job"]);
+    assert_eq!(machines.desktop.sessions().len(), 3);
+    assert_eq!(machines.laptop.sessions(), machines.desktop.sessions());
+
+    commands::rename(
+        &machines.laptop.ctx(),
+        "job".to_string(),
+        "laptop".to_string(),
+    )
+    .unwrap();
+    commands::rename(
+        &machines.desktop.ctx(),
+        "job".to_string(),
+        "desktop".to_string(),
+    )
+    .unwrap();
+    machines.sync_laptop();
+    let report = machines.sync_desktop();
+    assert_eq!(report.conflicts.len(), 1);
+    assert!(report.conflicts[0].contains("so it was named \"desktop\""));
+    machines.sync_laptop();
+    assert_eq!(names(&machines.laptop), ["desktop"]);
+    assert_eq!(machines.laptop.sessions().len(), 3);
+}
+
+#[test]
+fn sessions_written_without_ids_get_the_same_ids() {
+    let legacy = bincode::serialize(&(
+        Some(at(20, "11:00").timestamp()),
+        vec![(
+            at(10, "09:00").timestamp(),
+            at(10, "10:00").timestamp(),
+            "notes",
+        )],
+        "ongoing",
+    ))
+    .unwrap();
+    let (laptop, desktop) = (MemoryStorage::default(), MemoryStorage::default());
+    for storage in [&laptop, &desktop] {
+        storage.write("0", &legacy).unwrap();
+    }
+    let read = |storage: &MemoryStorage| Activity::read(storage, 0).unwrap().sessions[0].clone();
+    assert_eq!(read(&laptop).notes, "notes");
+    assert_eq!(read(&laptop).id, read(&laptop).id);
+    assert_eq!(read(&laptop).id, read(&desktop).id);
+}